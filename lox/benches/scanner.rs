@@ -0,0 +1,37 @@
+//! Times the scanner over large generated sources, run with `cargo bench`.
+//! Prints the fastest of a few runs of each case.
+
+use std::time::{Duration, Instant};
+
+use lox::scanner::{Scanner, TokenKind};
+
+const RUNS: usize = 10;
+
+fn bench(name: &str, mut run: impl FnMut() -> usize) {
+    let mut fastest = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        std::hint::black_box(run());
+        fastest = fastest.min(start.elapsed());
+    }
+    println!("{name:<24} {fastest:?}");
+}
+
+fn main() {
+    let identifiers: String = (0..100_000).map(|i| format!("ident_{i} ")).collect();
+    bench("100 000 identifiers", || {
+        Scanner::new(&identifiers)
+            .map(|t| t.expect("All tokens are valid"))
+            .filter(|t| t.tipo == TokenKind::Identifier)
+            .count()
+    });
+
+    let strings: String = (0..100_000).map(|i| format!("\"string {i}\" ")).collect();
+    bench("100 000 string literals", || {
+        Scanner::new(&strings)
+            .map(|t| t.expect("All tokens are valid"))
+            .filter(|t| t.tipo == TokenKind::String)
+            .map(|t| t.string_value(&strings).len())
+            .sum()
+    });
+}
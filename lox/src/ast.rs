@@ -1,11 +1,13 @@
-use crate::span::Span;
+use crate::{span::Span, visit::Visitor};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryKind {
     Plus,
     Minus,
     Star,
     Slash,
+    Pow,
     Mod,
     BangEqual,
     Equal,
@@ -14,29 +16,827 @@ pub enum BinaryKind {
     GreaterEqual,
     Less,
     LessEqual,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+/// The short-circuiting operators, kept apart from [`BinaryKind`] because
+/// their right operand is not always evaluated.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LogicalKind {
     And,
     Or,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryKind {
     Minus,
     Bang,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Expression {
     pub span: Span,
+    #[cfg_attr(feature = "serde", serde(flatten))]
     pub item: ExpressionItem,
 }
 
-#[derive(Debug, PartialEq)]
+/// Serialized as `{"kind": "Binary", "value": [lhs, rhs, "Plus", span]}`,
+/// with the fields of the variant under `value`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "value")
+)]
 pub enum ExpressionItem {
-    Binary(Box<Expression>, Box<Expression>, BinaryKind),
+    /// `lhs operator rhs`, the span is the operator's.
+    Binary(Box<Expression>, Box<Expression>, BinaryKind, Span),
+    Logical(Box<Expression>, Box<Expression>, LogicalKind),
     Unary(Box<Expression>, UnaryKind),
     Number(f64),
     String(String),
     Bool(bool),
     Nil,
     Grouping(Box<Expression>),
+    /// A reference to the variable with this name.
+    Variable(String),
+    /// `callee(args)`, `paren_span` is the closing paren.
+    Call {
+        callee: Box<Expression>,
+        paren_span: Span,
+        args: Vec<Expression>,
+    },
+    /// `name = value`
+    Assign {
+        name: Identifier,
+        value: Box<Expression>,
+    },
+    /// `object.name`
+    Get {
+        object: Box<Expression>,
+        name: Identifier,
+    },
+    /// `object.name = value`
+    Set {
+        object: Box<Expression>,
+        name: Identifier,
+        value: Box<Expression>,
+    },
+    /// `a, b, c`, results in the last expression.
+    Comma(Vec<Expression>),
+    /// `this`
+    This,
+    /// `super.method`
+    Super {
+        keyword_span: Span,
+        method: Identifier,
+    },
+    /// `fun (params) { body }`, a function without a name.
+    Lambda {
+        params: Vec<Identifier>,
+        body: Vec<Statement>,
+    },
+}
+
+/// Serialized like [`ExpressionItem`], `{"kind": "Expression", "value": ..}`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "value")
+)]
+pub enum Statement {
+    /// An expression evaluated for its side effects, `expr;`.
+    Expression(Expression),
+    /// `print a, b, c;`, printing the values separated by spaces.
+    Print(Vec<Expression>),
+    /// `var name;` or `var name = initializer;`
+    Var {
+        name: Identifier,
+        initializer: Option<Expression>,
+    },
+    /// `{ statements }`, with its own scope.
+    Block(Vec<Statement>),
+    /// `fun name(params) { body }`
+    Function(Function),
+    /// `class Name < Superclass { methods }`
+    Class {
+        name: Identifier,
+        superclass: Option<Identifier>,
+        methods: Vec<Function>,
+    },
+    /// `return;` or `return value;`. The keyword span is kept so errors can
+    /// point at a `return` used outside of a function.
+    Return {
+        keyword_span: Span,
+        value: Option<Expression>,
+    },
+    /// `break;`, holding the keyword span.
+    Break(Span),
+    /// `continue;`, holding the keyword span.
+    Continue(Span),
+    /// `while (condition) body`, `for` loops are desugared into it. The
+    /// `increment` of a `for` runs after each iteration, even one cut short
+    /// by `continue`. Appended to the body it would be skipped, `continue`
+    /// unwinds the whole body.
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        increment: Option<Expression>,
+    },
+}
+
+/// A name as written in the source, e.g. the variable in `var x;`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Identifier {
+    pub span: Span,
+    pub name: String,
+}
+
+/// `name(params) { body }`, the part shared by functions and methods.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Function {
+    pub name: Identifier,
+    pub params: Vec<Identifier>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+impl std::fmt::Display for BinaryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinaryKind::Plus => "+",
+            BinaryKind::Minus => "-",
+            BinaryKind::Star => "*",
+            BinaryKind::Slash => "/",
+            BinaryKind::Pow => "**",
+            BinaryKind::Mod => "%",
+            BinaryKind::BangEqual => "!=",
+            BinaryKind::Equal => "=",
+            BinaryKind::EqualEqual => "==",
+            BinaryKind::Greater => ">",
+            BinaryKind::GreaterEqual => ">=",
+            BinaryKind::Less => "<",
+            BinaryKind::LessEqual => "<=",
+            BinaryKind::BitAnd => "&",
+            BinaryKind::BitOr => "|",
+            BinaryKind::BitXor => "^",
+            BinaryKind::Shl => "<<",
+            BinaryKind::Shr => ">>",
+        })
+    }
+}
+
+impl std::fmt::Display for LogicalKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogicalKind::And => "and",
+            LogicalKind::Or => "or",
+        })
+    }
+}
+
+impl std::fmt::Display for UnaryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UnaryKind::Minus => "-",
+            UnaryKind::Bang => "!",
+        })
+    }
+}
+
+impl BinaryKind {
+    /// How tightly the operator binds, higher binds tighter. The parser
+    /// binds operators by these same levels. As in C, `&`, `^` and `|` bind
+    /// looser than `==`, so `a & b == c` is `a & (b == c)`.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryKind::Equal => 1,
+            BinaryKind::BitOr => 4,
+            BinaryKind::BitXor => 5,
+            BinaryKind::BitAnd => 6,
+            BinaryKind::EqualEqual | BinaryKind::BangEqual => 7,
+            BinaryKind::Greater
+            | BinaryKind::GreaterEqual
+            | BinaryKind::Less
+            | BinaryKind::LessEqual => 8,
+            BinaryKind::Shl | BinaryKind::Shr => 9,
+            BinaryKind::Plus | BinaryKind::Minus => 10,
+            BinaryKind::Star | BinaryKind::Slash | BinaryKind::Mod => 11,
+            BinaryKind::Pow => UnaryKind::PRECEDENCE + 1,
+        }
+    }
+
+    pub fn is_right_associative(&self) -> bool {
+        matches!(self, BinaryKind::Pow | BinaryKind::Equal)
+    }
+}
+
+impl LogicalKind {
+    /// See [`BinaryKind::precedence`], `or` binds looser than `and` and both
+    /// looser than any binary operator.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            LogicalKind::Or => 2,
+            LogicalKind::And => 3,
+        }
+    }
+}
+
+impl UnaryKind {
+    pub const PRECEDENCE: u8 = 12;
+}
+
+impl Expression {
+    fn precedence(&self) -> u8 {
+        match &self.item {
+            ExpressionItem::Binary(_, _, kind, _) => kind.precedence(),
+            ExpressionItem::Logical(_, _, kind) => kind.precedence(),
+            ExpressionItem::Unary(..) => UnaryKind::PRECEDENCE,
+            // Looser than any operator, even `=`
+            ExpressionItem::Comma(_) => 0,
+            ExpressionItem::Assign { .. } | ExpressionItem::Set { .. } => {
+                BinaryKind::Equal.precedence()
+            }
+            _ => u8::MAX,
+        }
+    }
+
+    /// Writes `self` as an operand that must bind at least as tight as
+    /// `precedence`, wrapping it in parentheses otherwise.
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, precedence: u8) -> std::fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+/// Prints the expression back as Lox source, adding only the parentheses
+/// that precedence and associativity require.
+///
+/// Lox strings have no escapes, so strings are printed between quotes as
+/// they are. One holding a `"`, which only a tree built by hand can have,
+/// doesn't print back to source that parses.
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.item {
+            ExpressionItem::Binary(lhs, rhs, kind, _) => {
+                let precedence = kind.precedence();
+                let (lhs_min, rhs_min) = match kind {
+                    // The right side of `**` is parsed as a unary
+                    BinaryKind::Pow => (precedence + 1, UnaryKind::PRECEDENCE),
+                    _ if kind.is_right_associative() => (precedence + 1, precedence),
+                    _ => (precedence, precedence + 1),
+                };
+
+                lhs.fmt_operand(f, lhs_min)?;
+                write!(f, " {kind} ")?;
+                rhs.fmt_operand(f, rhs_min)
+            }
+            ExpressionItem::Logical(lhs, rhs, kind) => {
+                let precedence = kind.precedence();
+                lhs.fmt_operand(f, precedence)?;
+                write!(f, " {kind} ")?;
+                rhs.fmt_operand(f, precedence + 1)
+            }
+            ExpressionItem::Unary(expr, kind) => {
+                write!(f, "{kind}")?;
+                expr.fmt_operand(f, UnaryKind::PRECEDENCE)
+            }
+            ExpressionItem::Grouping(expr) => write!(f, "({expr})"),
+            ExpressionItem::Number(n) => write!(f, "{n}"),
+            ExpressionItem::String(s) => write!(f, "\"{s}\""),
+            ExpressionItem::Bool(b) => write!(f, "{b}"),
+            ExpressionItem::Nil => write!(f, "nil"),
+            ExpressionItem::Variable(name) => write!(f, "{name}"),
+            ExpressionItem::Call { callee, args, .. } => {
+                callee.fmt_operand(f, u8::MAX)?;
+                write!(f, "(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    arg.fmt_operand(f, BinaryKind::Equal.precedence())?;
+                }
+                write!(f, ")")
+            }
+            ExpressionItem::Assign { name, value } => {
+                write!(f, "{} = ", name.name)?;
+                value.fmt_operand(f, BinaryKind::Equal.precedence())
+            }
+            ExpressionItem::Get { object, name } => {
+                object.fmt_operand(f, u8::MAX)?;
+                write!(f, ".{}", name.name)
+            }
+            ExpressionItem::Comma(exprs) => {
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    expr.fmt_operand(f, BinaryKind::Equal.precedence())?;
+                }
+                Ok(())
+            }
+            ExpressionItem::This => write!(f, "this"),
+            ExpressionItem::Super { method, .. } => write!(f, "super.{}", method.name),
+            ExpressionItem::Set {
+                object,
+                name,
+                value,
+            } => {
+                object.fmt_operand(f, u8::MAX)?;
+                write!(f, ".{} = ", name.name)?;
+                value.fmt_operand(f, BinaryKind::Equal.precedence())
+            }
+            ExpressionItem::Lambda { params, body } => {
+                write!(f, "fun ")?;
+                fmt_function(f, params, body)
+            }
+        }
+    }
+}
+
+/// Prints the statement back as Lox source, on a single line.
+impl std::fmt::Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statement::Expression(expr) => write!(f, "{expr};"),
+            Statement::Print(values) => {
+                write!(f, "print ")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, ";")
+            }
+            Statement::Var {
+                name,
+                initializer: Some(expr),
+            } => write!(f, "var {} = {expr};", name.name),
+            Statement::Var {
+                name,
+                initializer: None,
+            } => write!(f, "var {};", name.name),
+            Statement::Block(statements) => fmt_block(f, statements),
+            Statement::Function(function) => write!(f, "fun {function}"),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                write!(f, "class {}", name.name)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass.name)?;
+                }
+                write!(f, " {{")?;
+                for method in methods {
+                    write!(f, " {method}")?;
+                }
+                write!(f, " }}")
+            }
+            Statement::Return { value: None, .. } => write!(f, "return;"),
+            Statement::Return {
+                value: Some(value), ..
+            } => write!(f, "return {value};"),
+            Statement::Break(_) => write!(f, "break;"),
+            Statement::Continue(_) => write!(f, "continue;"),
+            Statement::While {
+                condition,
+                body,
+                increment: None,
+            } => write!(f, "while ({condition}) {body}"),
+            Statement::While {
+                condition,
+                body,
+                increment: Some(increment),
+            } => write!(f, "for (; {condition}; {increment}) {body}"),
+        }
+    }
+}
+
+/// `name(params) { body }`
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name.name)?;
+        fmt_function(f, &self.params, &self.body)
+    }
+}
+
+/// `(params) { body }`, what follows the name of a function.
+fn fmt_function(
+    f: &mut std::fmt::Formatter<'_>,
+    params: &[Identifier],
+    body: &[Statement],
+) -> std::fmt::Result {
+    let params: Vec<_> = params.iter().map(|p| p.name.as_str()).collect();
+    write!(f, "({}) ", params.join(", "))?;
+    fmt_block(f, body)
+}
+
+/// `{ statements }`, or `{}` when there are none.
+fn fmt_block(f: &mut std::fmt::Formatter<'_>, statements: &[Statement]) -> std::fmt::Result {
+    if statements.is_empty() {
+        return write!(f, "{{}}");
+    }
+
+    write!(f, "{{")?;
+    for statement in statements {
+        write!(f, " {statement}")?;
+    }
+    write!(f, " }}")
+}
+
+/// Renders `expr` in the canonical S-expression form, e.g. `(* (group (+ 1 2)) 3)`.
+pub fn print(expr: &Expression) -> String {
+    let mut printer = Printer::default();
+    printer.visit_expression(expr);
+    printer.out
+}
+
+/// Renders every statement of `program` on its own line, see [`print`].
+pub fn print_program(program: &Program) -> String {
+    let mut out = String::new();
+    for statement in &program.statements {
+        out.push_str(&print_statement(statement));
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders `program` as indented JSON. Every node carries its `span` as
+/// `{start, end}` and is tagged with the name of its variant, see
+/// [`ExpressionItem`].
+#[cfg(feature = "serde")]
+pub fn to_json_pretty(program: &Program) -> String {
+    serde_json::to_string_pretty(program).expect("The AST is serializable")
+}
+
+fn print_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Expression(expr) => print(expr),
+        Statement::Print(values) => {
+            let values: Vec<_> = values.iter().map(print).collect();
+            format!("(print {})", values.join(" "))
+        }
+        Statement::Var {
+            name,
+            initializer: Some(expr),
+        } => format!("(var {} {})", name.name, print(expr)),
+        Statement::Var {
+            name,
+            initializer: None,
+        } => format!("(var {})", name.name),
+        Statement::Break(_) => "(break)".to_string(),
+        Statement::Continue(_) => "(continue)".to_string(),
+        Statement::Return { value: None, .. } => "(return)".to_string(),
+        Statement::Return {
+            value: Some(value), ..
+        } => format!("(return {})", print(value)),
+        Statement::While {
+            condition,
+            body,
+            increment: None,
+        } => format!("(while {} {})", print(condition), print_statement(body)),
+        Statement::While {
+            condition,
+            body,
+            increment: Some(increment),
+        } => format!(
+            "(while {} {} {})",
+            print(condition),
+            print_statement(body),
+            print(increment)
+        ),
+        Statement::Function(function) => format!("(fun {})", print_function(function)),
+        Statement::Class {
+            name,
+            superclass,
+            methods,
+        } => {
+            let mut out = format!("(class {}", name.name);
+            if let Some(superclass) = superclass {
+                out.push_str(&format!(" < {}", superclass.name));
+            }
+            for method in methods {
+                out.push_str(&format!(" ({})", print_function(method)));
+            }
+            out.push(')');
+            out
+        }
+        Statement::Block(statements) => {
+            let mut out = String::from("(block");
+            for statement in statements {
+                out.push(' ');
+                out.push_str(&print_statement(statement));
+            }
+            out.push(')');
+            out
+        }
+    }
+}
+
+/// `name (params) body..`, the caller adds the parentheses around it.
+fn print_function(function: &Function) -> String {
+    format!(
+        "{} {}",
+        function.name.name,
+        print_lambda(&function.params, &function.body)
+    )
+}
+
+/// `(params) body..`, see [`print_function`].
+fn print_lambda(params: &[Identifier], body: &[Statement]) -> String {
+    let params: Vec<_> = params.iter().map(|p| p.name.as_str()).collect();
+    let mut out = format!("({})", params.join(" "));
+    for statement in body {
+        out.push(' ');
+        out.push_str(&print_statement(statement));
+    }
+    out
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+}
+
+impl Printer {
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expression]) {
+        self.out.push('(');
+        self.out.push_str(name);
+        for expr in exprs {
+            self.out.push(' ');
+            self.visit_expression(expr);
+        }
+        self.out.push(')');
+    }
+}
+
+impl Visitor for Printer {
+    fn visit_binary(&mut self, lhs: &Expression, rhs: &Expression, kind: &BinaryKind) {
+        self.parenthesize(&kind.to_string(), &[lhs, rhs]);
+    }
+
+    fn visit_logical(&mut self, lhs: &Expression, rhs: &Expression, kind: &LogicalKind) {
+        self.parenthesize(&kind.to_string(), &[lhs, rhs]);
+    }
+
+    fn visit_unary(&mut self, expr: &Expression, kind: &UnaryKind) {
+        self.parenthesize(&kind.to_string(), &[expr]);
+    }
+
+    fn visit_grouping(&mut self, expr: &Expression) {
+        self.parenthesize("group", &[expr]);
+    }
+
+    fn visit_variable(&mut self, variable: &Expression) {
+        self.out.push_str(&variable.to_string());
+    }
+
+    fn visit_assign(&mut self, name: &Identifier, value: &Expression) {
+        self.out.push_str(&format!("(= {} ", name.name));
+        self.visit_expression(value);
+        self.out.push(')');
+    }
+
+    fn visit_call(&mut self, callee: &Expression, args: &[Expression]) {
+        let exprs: Vec<_> = std::iter::once(callee).chain(args).collect();
+        self.parenthesize("call", &exprs);
+    }
+
+    fn visit_comma(&mut self, exprs: &[Expression]) {
+        let exprs: Vec<_> = exprs.iter().collect();
+        self.parenthesize("comma", &exprs);
+    }
+
+    fn visit_this(&mut self, this: &Expression) {
+        self.out.push_str(&this.to_string());
+    }
+
+    fn visit_super(&mut self, method: &Identifier) {
+        self.out.push_str(&format!("(super {})", method.name));
+    }
+
+    fn visit_get(&mut self, object: &Expression, name: &Identifier) {
+        self.out.push_str("(get ");
+        self.visit_expression(object);
+        self.out.push_str(&format!(" {})", name.name));
+    }
+
+    fn visit_set(&mut self, object: &Expression, name: &Identifier, value: &Expression) {
+        self.out.push_str("(set ");
+        self.visit_expression(object);
+        self.out.push_str(&format!(" {} ", name.name));
+        self.visit_expression(value);
+        self.out.push(')');
+    }
+
+    fn visit_lambda(&mut self, params: &[Identifier], body: &[Statement]) {
+        self.out
+            .push_str(&format!("(fun {})", print_lambda(params, body)));
+    }
+
+    fn visit_literal(&mut self, literal: &Expression) {
+        match &literal.item {
+            ExpressionItem::String(s) => self.out.push_str(&format!("{s:?}")),
+            _ => self.out.push_str(&literal.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{print, BinaryKind, Expression, ExpressionItem, UnaryKind};
+    use crate::{
+        span::Span,
+        visit::{walk_expression, Visitor},
+    };
+
+    fn expr(item: ExpressionItem) -> Expression {
+        Expression {
+            span: Span::default(),
+            item,
+        }
+    }
+
+    fn binary(lhs: Expression, rhs: Expression, kind: BinaryKind) -> Expression {
+        expr(ExpressionItem::Binary(
+            Box::new(lhs),
+            Box::new(rhs),
+            kind,
+            Span::default(),
+        ))
+    }
+
+    #[test]
+    fn display_strings_as_they_are() {
+        let string = |s: &str| expr(ExpressionItem::String(s.to_string())).to_string();
+        assert_eq!(string("a\\b\n"), "\"a\\b\n\"");
+        // Not escaped, there is nothing it could be escaped with
+        assert_eq!(string("say \"hi\""), "\"say \"hi\"\"");
+    }
+
+    #[test]
+    fn print_literals() {
+        assert_eq!(print(&expr(ExpressionItem::Number(1.0))), "1");
+        assert_eq!(print(&expr(ExpressionItem::Number(2.5))), "2.5");
+        assert_eq!(
+            print(&expr(ExpressionItem::String("hi".to_string()))),
+            "\"hi\""
+        );
+        assert_eq!(print(&expr(ExpressionItem::Bool(true))), "true");
+        assert_eq!(print(&expr(ExpressionItem::Bool(false))), "false");
+        assert_eq!(print(&expr(ExpressionItem::Nil)), "nil");
+    }
+
+    #[test]
+    fn print_unary() {
+        let minus = expr(ExpressionItem::Unary(
+            Box::new(expr(ExpressionItem::Number(5.0))),
+            UnaryKind::Minus,
+        ));
+        let bang = expr(ExpressionItem::Unary(Box::new(minus), UnaryKind::Bang));
+
+        assert_eq!(print(&bang), "(! (- 5))");
+    }
+
+    #[test]
+    fn print_binary_and_grouping() {
+        let sum = binary(
+            expr(ExpressionItem::Number(1.0)),
+            expr(ExpressionItem::Number(2.0)),
+            BinaryKind::Plus,
+        );
+        let product = binary(
+            expr(ExpressionItem::Grouping(Box::new(sum))),
+            expr(ExpressionItem::Number(3.0)),
+            BinaryKind::Star,
+        );
+
+        assert_eq!(print(&product), "(* (group (+ 1 2)) 3)");
+    }
+
+    #[test]
+    fn display_adds_required_parentheses() {
+        let sum = binary(
+            expr(ExpressionItem::Number(1.0)),
+            expr(ExpressionItem::Number(2.0)),
+            BinaryKind::Plus,
+        );
+        let product = binary(sum, expr(ExpressionItem::Number(3.0)), BinaryKind::Star);
+        assert_eq!(product.to_string(), "(1 + 2) * 3");
+
+        let difference = binary(
+            expr(ExpressionItem::Number(1.0)),
+            binary(
+                expr(ExpressionItem::Number(2.0)),
+                expr(ExpressionItem::Number(3.0)),
+                BinaryKind::Minus,
+            ),
+            BinaryKind::Minus,
+        );
+        assert_eq!(difference.to_string(), "1 - (2 - 3)");
+
+        let negated = expr(ExpressionItem::Unary(
+            Box::new(binary(
+                expr(ExpressionItem::Number(1.0)),
+                expr(ExpressionItem::Number(2.0)),
+                BinaryKind::Plus,
+            )),
+            UnaryKind::Minus,
+        ));
+        assert_eq!(negated.to_string(), "-(1 + 2)");
+
+        let power = binary(
+            expr(ExpressionItem::Unary(
+                Box::new(expr(ExpressionItem::Number(2.0))),
+                UnaryKind::Minus,
+            )),
+            expr(ExpressionItem::Number(2.0)),
+            BinaryKind::Pow,
+        );
+        assert_eq!(power.to_string(), "(-2) ** 2");
+    }
+
+    #[derive(Default)]
+    struct NodeCounter {
+        count: usize,
+    }
+
+    impl Visitor for NodeCounter {
+        fn visit_expression(&mut self, expr: &Expression) {
+            self.count += 1;
+            walk_expression(self, expr);
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_every_node() {
+        // (! (group (+ 1 (- 2))))
+        let tree = expr(ExpressionItem::Unary(
+            Box::new(expr(ExpressionItem::Grouping(Box::new(binary(
+                expr(ExpressionItem::Number(1.0)),
+                expr(ExpressionItem::Unary(
+                    Box::new(expr(ExpressionItem::Number(2.0))),
+                    UnaryKind::Minus,
+                )),
+                BinaryKind::Plus,
+            ))))),
+            UnaryKind::Bang,
+        ));
+
+        let mut counter = NodeCounter::default();
+        counter.visit_expression(&tree);
+
+        assert_eq!(counter.count, 6);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_json() {
+        let sum = Expression {
+            span: Span::from(0..5),
+            item: ExpressionItem::Binary(
+                Box::new(Expression {
+                    span: Span::from(0..1),
+                    item: ExpressionItem::Number(1.0),
+                }),
+                Box::new(Expression {
+                    span: Span::from(4..5),
+                    item: ExpressionItem::Number(2.0),
+                }),
+                BinaryKind::Plus,
+                Span::from(2..3),
+            ),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&sum).expect("The AST is serializable"),
+            concat!(
+                r#"{"span":{"start":0,"end":5},"kind":"Binary","value":["#,
+                r#"{"span":{"start":0,"end":1},"kind":"Number","value":1.0},"#,
+                r#"{"span":{"start":4,"end":5},"kind":"Number","value":2.0},"#,
+                r#""Plus",{"start":2,"end":3}]}"#
+            )
+        );
+    }
 }
@@ -1,11 +1,79 @@
 use crate::span::{Location, Span};
 use owo_colors::OwoColorize;
+use unicode_width::UnicodeWidthChar;
+
+/// When diagnostics and other output use ANSI colors, set with `--color=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    Always,
+    /// Color terminals, unless `NO_COLOR` is set to anything non-empty, see
+    /// <https://no-color.org>.
+    #[default]
+    Auto,
+    Never,
+}
+
+impl ColorMode {
+    pub fn from_flag(mode: &str) -> Option<ColorMode> {
+        match mode {
+            "always" => Some(ColorMode::Always),
+            "auto" => Some(ColorMode::Auto),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    /// Makes every `if_supports_color` call follow this mode.
+    pub fn apply(self) {
+        match self {
+            ColorMode::Always => owo_colors::set_override(true),
+            ColorMode::Never => owo_colors::set_override(false),
+            ColorMode::Auto => {
+                if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+                    owo_colors::set_override(false);
+                }
+            }
+        }
+    }
+}
+
+/// How serious a [`Diagnostic`] is. Only errors make compilation fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+            Severity::Note => "Note",
+        }
+    }
+
+    fn style(self) -> owo_colors::Style {
+        match self {
+            Severity::Error => owo_colors::Style::new().bold().red(),
+            Severity::Warning => owo_colors::Style::new().bold().yellow(),
+            Severity::Note => owo_colors::Style::new().bold().cyan(),
+        }
+    }
+}
 
 pub struct Diagnostic<'src> {
+    severity: Severity,
     msg: String,
     source: &'src str,
     path: &'src std::path::Path,
     span: Span,
+    /// Secondary spans shown along with the main one, each with a message.
+    labels: Vec<(Span, String)>,
+    /// The lines shown around the span, `-1..1` is one line before and after.
+    context: std::ops::Range<i16>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -18,14 +86,49 @@ struct Context<'src> {
 impl<'src> Diagnostic<'src> {
     pub fn new(source: &'src str, path: &'src std::path::Path, span: Span, msg: String) -> Self {
         Self {
+            severity: Severity::Error,
             msg,
             source,
             path,
             span,
+            labels: Vec::new(),
+            context: -1..1,
         }
     }
 
-    fn get_context(&self, n: std::ops::Range<i16>) -> Vec<Context> {
+    /// Also underlines `span`, on its first line, with `msg` next to it.
+    pub fn with_label(mut self, span: Span, msg: String) -> Self {
+        self.labels.push((span, msg));
+        self
+    }
+
+    pub fn with_context(mut self, context: std::ops::Range<i16>) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn start(&self) -> Location {
+        self.span.get_start_location(self.source)
+    }
+
+    pub fn end(&self) -> Location {
+        self.span.get_end_location(self.source)
+    }
+
+    fn get_context(&self, n: std::ops::Range<i16>) -> Vec<Context<'_>> {
         assert!(n.start <= 0);
         assert!(n.end >= 0);
 
@@ -35,19 +138,26 @@ impl<'src> Diagnostic<'src> {
         let Location {
             line: start_line,
             col: start_col,
-        } = self.span.get_start_location(self.source);
-        let Location { line: end_line, .. } = self.span.get_end_location(self.source);
+        } = self.start();
+        let Location { line: end_line, .. } = self.end();
+        // The labels are shown too, wherever they are
+        let label_lines = self
+            .labels
+            .iter()
+            .map(|(span, _)| span.get_start_location(self.source).line);
+        let first_line = label_lines.clone().fold(start_line, usize::min);
+        let last_line = label_lines.fold(end_line, usize::max);
 
-        let context_start = start_line
+        let context_start = first_line
             .checked_sub(n.start.unsigned_abs() as usize)
             .unwrap_or(1);
-        let context_end = n_lines.min(end_line + n.end as usize);
+        let context_end = n_lines.min(last_line + n.end as usize);
 
-        let span_lines = self.source[self.span.range()]
+        // Highlights count characters, like the columns they start at
+        let mut left = self.source[self.span.range()]
             .chars()
-            .filter(|c| c == &'\n')
+            .filter(|c| c != &'\n')
             .count();
-        let mut left = self.span.len() - span_lines;
         for (line_num, src) in self
             .source
             .lines()
@@ -65,8 +175,13 @@ impl<'src> Diagnostic<'src> {
                         0
                     };
 
-                    let end = left.min(src.len() - start);
-                    left -= end;
+                    // An empty span, like the place of a missing `;`, still
+                    // gets a caret, even past the end of the line
+                    let end = match self.span.is_empty() {
+                        true => 1,
+                        false => left.min(src.chars().count().saturating_sub(start)),
+                    };
+                    left = left.saturating_sub(end);
 
                     start..start + end
                 }),
@@ -75,32 +190,149 @@ impl<'src> Diagnostic<'src> {
 
         res
     }
+}
+
+/// Whether what is written to `stream` gets colors, following `--color=`
+/// and what the terminal supports.
+fn supports_color(stream: owo_colors::Stream) -> bool {
+    !"".if_supports_color(stream, |s| s.bold())
+        .to_string()
+        .is_empty()
+}
+
+/// Collects diagnostics so they are shown together, and in order, once
+/// compilation is done.
+pub struct DiagnosticSink<'src> {
+    diagnostics: Vec<Diagnostic<'src>>,
+    /// Whether [`DiagnosticSink::render`] and [`DiagnosticSink::summary`]
+    /// use colors, decided once for the stream they are written to.
+    color: bool,
+}
+
+impl<'src> DiagnosticSink<'src> {
+    /// A sink whose diagnostics are written to `stream`.
+    pub fn new(stream: owo_colors::Stream) -> Self {
+        DiagnosticSink {
+            diagnostics: Vec::new(),
+            color: supports_color(stream),
+        }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic<'src>) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Whether any diagnostic is an error, warnings and notes don't count.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diag| diag.severity() == Severity::Error)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diag| diag.severity() == severity)
+            .count()
+    }
+
+    /// A closing line like `error: aborting due to 3 errors; 1 warning`,
+    /// `None` when there are no errors nor warnings.
+    pub fn summary(&self) -> Option<String> {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {what}"),
+            n => format!("{n} {what}s"),
+        };
+        let label = |severity: Severity| {
+            let label = severity.label().to_lowercase();
+            match self.color {
+                true => label.style(severity.style()).to_string(),
+                false => label,
+            }
+        };
+
+        match (self.count(Severity::Error), self.count(Severity::Warning)) {
+            (0, 0) => None,
+            (0, warnings) => Some(format!(
+                "{}: {} emitted",
+                label(Severity::Warning),
+                plural(warnings, "warning")
+            )),
+            (errors, 0) => Some(format!(
+                "{}: aborting due to {}",
+                label(Severity::Error),
+                plural(errors, "error")
+            )),
+            (errors, warnings) => Some(format!(
+                "{}: aborting due to {}; {}",
+                label(Severity::Error),
+                plural(errors, "error"),
+                plural(warnings, "warning")
+            )),
+        }
+    }
 
-    pub fn out(self) {
-        println!("{self}")
+    /// Renders `diagnostic` with the colors of this sink.
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        owo_colors::with_override(self.color, || diagnostic.to_string())
     }
 
-    pub fn err(self) {
-        eprintln!("{self}")
+    /// Takes every diagnostic out, in the order they were pushed.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, Diagnostic<'src>> {
+        self.diagnostics.drain(..)
     }
 }
 
+/// Renders `diagnostics` as a JSON array of
+/// `{file, start: {line, col}, end: {line, col}, message, severity}` objects.
+#[cfg(feature = "serde")]
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    #[derive(serde::Serialize)]
+    struct Json<'a> {
+        file: std::borrow::Cow<'a, str>,
+        start: Location,
+        end: Location,
+        message: &'a str,
+        severity: &'a str,
+    }
+
+    let diagnostics: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| Json {
+            file: diag.path.to_string_lossy(),
+            start: diag.start(),
+            end: diag.end(),
+            message: diag.message(),
+            severity: match diag.severity() {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note => "note",
+            },
+        })
+        .collect();
+
+    serde_json::to_string(&diagnostics).expect("Diagnostics are serializable")
+}
+
 impl std::fmt::Display for Diagnostic<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Location { line, col } = self.span.get_start_location(self.source);
+        let Location { line, col } = self.start();
         writeln!(
             f,
-            "{error_rojo} at {file}:{line}:{col}: {error_msg}",
-            error_rojo = "Error".if_supports_color(owo_colors::Stream::Stdout, |s| {
-                s.style(owo_colors::Style::new().bold().red())
-            }),
+            "{label} at {file}:{line}:{col}: {error_msg}",
+            label = self
+                .severity()
+                .label()
+                .if_supports_color(owo_colors::Stream::Stdout, |s| {
+                    s.style(self.severity().style())
+                }),
             file = self.path.display(),
             line = line,
             col = col,
-            error_msg = self.msg
+            error_msg = self.message()
         )?;
 
-        let lines = self.get_context(-1..1);
+        let lines = self.get_context(self.context.clone());
         for Context {
             source,
             line,
@@ -116,17 +348,63 @@ impl std::fmt::Display for Diagnostic<'_> {
                 }),
             )?;
             writeln!(f, "{source}")?;
-            if let Some(range) = highlight {
+
+            let primary = highlight.clone().map(|range| (range, '^', None));
+            let labels = self.labels.iter().filter_map(|(span, msg)| {
+                let Location { line: start, col } = span.get_start_location(self.source);
+                (start == *line).then(|| {
+                    let start = col - 1;
+                    let len = self.source[span.range()]
+                        .chars()
+                        .take_while(|c| c != &'\n')
+                        .count();
+                    (start..start + len.max(1), '-', Some(msg))
+                })
+            });
+            let underlines: Vec<_> = primary.into_iter().chain(labels).collect();
+            for (i, (range, marker, msg)) in underlines.iter().enumerate() {
+                let style = match marker {
+                    '^' => owo_colors::Style::new().bold().yellow(),
+                    _ => owo_colors::Style::new().bold().blue(),
+                };
+                let (prefix, rest) = source.split_at(
+                    source
+                        .char_indices()
+                        .nth(range.start)
+                        .map_or(source.len(), |(i, _)| i),
+                );
+                // Tabs are kept so the carets line up whatever their width is,
+                // other characters take as many columns as they are displayed in
+                let padding: String = prefix
+                    .chars()
+                    .map(|c| match c {
+                        '\t' => "\t".to_string(),
+                        c => " ".repeat(c.width().unwrap_or(0)),
+                    })
+                    .collect();
+                let width = rest
+                    .chars()
+                    .take(range.len())
+                    .map(|c| c.width().unwrap_or(0))
+                    .sum::<usize>()
+                    .max(range.len().min(1));
                 write!(
                     f,
-                    "{}{}",
-                    " ".repeat(range.start + 8),
-                    "^".repeat(range.len())
-                        .if_supports_color(owo_colors::Stream::Stdout, |s| {
-                            s.style(owo_colors::Style::new().bold().yellow())
-                        }),
+                    "{}{padding}{}",
+                    " ".repeat(8),
+                    marker
+                        .to_string()
+                        .repeat(width)
+                        .if_supports_color(owo_colors::Stream::Stdout, |s| s.style(style)),
                 )?;
-                if lines.last().is_some_and(|l| l.line != *line) {
+                if let Some(msg) = msg {
+                    write!(
+                        f,
+                        " {}",
+                        msg.if_supports_color(owo_colors::Stream::Stdout, |s| s.style(style))
+                    )?;
+                }
+                if lines.last().is_some_and(|l| l.line != *line) || i + 1 < underlines.len() {
                     writeln!(f)?;
                 }
             }
@@ -140,11 +418,289 @@ impl std::fmt::Display for Diagnostic<'_> {
 mod test {
     use std::path::PathBuf;
 
+    use owo_colors::Stream;
+
     use crate::{
-        diag::{Context, Diagnostic},
+        diag::{Context, Diagnostic, DiagnosticSink, Severity},
         span::Span,
     };
 
+    #[test]
+    fn sink_keeps_order() {
+        let path = PathBuf::from("test.lox");
+        let source = "1 + nil;\nprint x;";
+        let mut sink = DiagnosticSink::new(Stream::Stderr);
+        assert!(!sink.has_errors());
+
+        sink.push(
+            Diagnostic::new(source, &path, Span::from(0..7), "first".to_string())
+                .with_severity(Severity::Warning),
+        );
+        assert!(!sink.has_errors());
+        sink.push(Diagnostic::new(
+            source,
+            &path,
+            Span::from(15..16),
+            "second".to_string(),
+        ));
+        assert!(sink.has_errors());
+
+        let rendered: Vec<_> = owo_colors::with_override(false, || {
+            sink.drain().map(|diag| diag.to_string()).collect()
+        });
+        assert_eq!(rendered.len(), 2);
+        assert!(rendered[0].starts_with("Warning at test.lox:1:1: first"));
+        assert!(rendered[1].starts_with("Error at test.lox:2:7: second"));
+        assert!(!sink.has_errors());
+    }
+
+    #[test]
+    fn sink_summary() {
+        let path = PathBuf::from("test.lox");
+        let source = "1;";
+        let diag = |severity| {
+            Diagnostic::new(source, &path, Span::from(0..1), String::new()).with_severity(severity)
+        };
+        let summary = |severities: &[Severity]| {
+            let mut sink = owo_colors::with_override(false, || DiagnosticSink::new(Stream::Stderr));
+            for &severity in severities {
+                sink.push(diag(severity));
+            }
+            sink.summary()
+        };
+
+        assert_eq!(summary(&[]), None);
+        assert_eq!(summary(&[Severity::Note]), None);
+        assert_eq!(
+            summary(&[Severity::Error]).as_deref(),
+            Some("error: aborting due to 1 error")
+        );
+        assert_eq!(
+            summary(&[Severity::Warning, Severity::Warning]).as_deref(),
+            Some("warning: 2 warnings emitted")
+        );
+        assert_eq!(
+            summary(&[
+                Severity::Error,
+                Severity::Warning,
+                Severity::Error,
+                Severity::Note,
+                Severity::Error
+            ])
+            .as_deref(),
+            Some("error: aborting due to 3 errors; 1 warning")
+        );
+    }
+
+    #[test]
+    fn sink_colors_are_decided_once() {
+        let path = PathBuf::from("test.lox");
+        let source = "1;";
+        let mut sink = owo_colors::with_override(true, || DiagnosticSink::new(Stream::Stderr));
+        let diag = || Diagnostic::new(source, &path, Span::from(0..1), "message".to_string());
+        sink.push(diag());
+
+        owo_colors::with_override(false, || {
+            assert!(sink
+                .render(&diag())
+                .starts_with("\x1b[31;1mError\x1b[0m at"));
+            assert_eq!(
+                sink.summary().as_deref(),
+                Some("\x1b[31;1merror\x1b[0m: aborting due to 1 error")
+            );
+        });
+
+        let sink = owo_colors::with_override(false, || DiagnosticSink::new(Stream::Stderr));
+        owo_colors::with_override(true, || {
+            assert!(sink
+                .render(&diag())
+                .starts_with("Error at test.lox:1:1: message\n"));
+        });
+    }
+
+    #[test]
+    fn caret_after_tabs() {
+        let path = PathBuf::from("test.lox");
+        let source = "{\n\t\tprint @;\n}";
+        let diag = Diagnostic::new(source, &path, Span::from(10..11), String::new());
+
+        owo_colors::with_override(false, || {
+            let rendered = diag.to_string();
+            let caret = rendered
+                .lines()
+                .find(|line| line.contains('^'))
+                .expect("The span is underlined");
+
+            assert_eq!(caret, "        \t\t      ^");
+        });
+    }
+
+    #[test]
+    fn caret_width_of_wide_characters() {
+        let path = PathBuf::from("test.lox");
+        let source = "var 变量 = 1;";
+        let span = Span::from(4..10);
+        assert_eq!(&source[span.range()], "变量");
+
+        let diag = Diagnostic::new(source, &path, span, String::new());
+
+        owo_colors::with_override(false, || {
+            let rendered = diag.to_string();
+            let caret = rendered
+                .lines()
+                .find(|line| line.contains('^'))
+                .expect("The span is underlined");
+
+            assert_eq!(caret, "            ^^^^");
+        });
+    }
+
+    #[test]
+    fn caret_after_wide_characters() {
+        let path = PathBuf::from("test.lox");
+        let source = "\"变量\" + @;";
+        let span = Span::from(11..12);
+        assert_eq!(&source[span.range()], "@");
+
+        let diag = Diagnostic::new(source, &path, span, String::new());
+
+        owo_colors::with_override(false, || {
+            let rendered = diag.to_string();
+            let caret = rendered
+                .lines()
+                .find(|line| line.contains('^'))
+                .expect("The span is underlined");
+
+            assert_eq!(caret, format!("{}^", " ".repeat(8 + 9)));
+        });
+    }
+
+    #[test]
+    fn context_window() {
+        let path = PathBuf::from("test.lox");
+        let source = "1;\n2;\n3;\n@;\n5;\n6;\n7;";
+        let diag = || Diagnostic::new(source, &path, Span::from(9..10), String::new());
+
+        owo_colors::with_override(false, || {
+            let narrow = diag().to_string();
+            assert_eq!(narrow.lines().count(), 1 + 3 + 1);
+            assert!(narrow.contains("   3 | 3;") && !narrow.contains("   2 | 2;"));
+
+            let wide = diag().with_context(-3..3).to_string();
+            assert_eq!(wide.lines().count(), 1 + 7 + 1);
+            assert!(wide.contains("   1 | 1;") && wide.contains("   7 | 7;"));
+        });
+    }
+
+    #[test]
+    fn severity_header() {
+        let path = PathBuf::from("test.lox");
+        let source = "1 < 2 < 3;";
+        let diag = |severity| {
+            Diagnostic::new(source, &path, Span::from(0..9), "message".to_string())
+                .with_severity(severity)
+                .to_string()
+        };
+
+        owo_colors::with_override(true, || {
+            assert!(diag(Severity::Warning)
+                .starts_with("\x1b[33;1mWarning\x1b[0m at test.lox:1:1: message\n"));
+            assert!(diag(Severity::Note).starts_with("\x1b[36;1mNote\x1b[0m at"));
+            assert!(diag(Severity::Error).starts_with("\x1b[31;1mError\x1b[0m at"));
+        });
+
+        owo_colors::with_override(false, || {
+            assert!(diag(Severity::Error).starts_with("Error at test.lox:1:1: message\n"));
+        });
+    }
+
+    #[test]
+    fn non_ascii_ctx() {
+        let source = "var ñandú = \"é\";\nñandú + @;";
+        let span = Span::from(30..31);
+        assert_eq!(&source[span.range()], "@");
+
+        let path = PathBuf::new();
+        let diag = Diagnostic::new(source, &path, span, String::new());
+
+        assert_eq!(
+            diag.get_context(0..0),
+            vec![Context {
+                source: "ñandú + @;",
+                line: 2,
+                highlight: Some(8..9)
+            }]
+        );
+
+        let span = Span::from(4..18);
+        assert_eq!(&source[span.range()], "ñandú = \"é\"");
+        let diag = Diagnostic::new(source, &path, span, String::new());
+
+        assert_eq!(
+            diag.get_context(0..0),
+            vec![Context {
+                source: "var ñandú = \"é\";",
+                line: 1,
+                highlight: Some(4..15)
+            }]
+        );
+        owo_colors::with_override(false, || {
+            let rendered = diag.to_string();
+            let caret = rendered
+                .lines()
+                .find(|line| line.contains('^'))
+                .expect("The span is underlined");
+
+            assert_eq!(caret, format!("{}{}", " ".repeat(8 + 4), "^".repeat(11)));
+        });
+    }
+
+    #[test]
+    fn caret_for_empty_span_at_end_of_line() {
+        let path = PathBuf::from("test.lox");
+
+        // In the middle of the file and at its very end
+        for (source, at) in [("var a = 1\nprint a;", 9), ("print a", 7)] {
+            let diag = Diagnostic::new(source, &path, Span::from(at..at), String::new());
+            assert_eq!(diag.get_context(0..0)[0].highlight, Some(at..at + 1));
+
+            owo_colors::with_override(false, || {
+                let rendered = diag.to_string();
+                let caret = rendered
+                    .lines()
+                    .find(|line| line.contains('^'))
+                    .expect("The empty span is pointed at");
+
+                assert_eq!(caret, format!("{}^", " ".repeat(8 + at)));
+            });
+        }
+    }
+
+    #[test]
+    fn secondary_labels() {
+        let path = PathBuf::from("test.lox");
+        let source = "(1 + (2 * 3;";
+        let diag = Diagnostic::new(source, &path, Span::from(11..12), "unclosed".to_string())
+            .with_label(Span::from(5..6), "opened here".to_string());
+
+        let rendered = owo_colors::with_override(false, || diag.to_string());
+        assert_eq!(
+            rendered,
+            "Error at test.lox:1:12: unclosed\n    1 | (1 + (2 * 3;\n                   ^\n             - opened here"
+        );
+    }
+
+    #[test]
+    fn labels_widen_the_context() {
+        let path = PathBuf::from("test.lox");
+        let source = "(\n1\n2\n3\n;";
+        let diag = Diagnostic::new(source, &path, Span::from(8..9), String::new())
+            .with_label(Span::from(0..1), "opened here".to_string());
+
+        let lines: Vec<_> = diag.get_context(0..0).iter().map(|c| c.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5]);
+    }
+
     #[test]
     fn single_line_ctx() {
         let source = "...\n...\n.@.\n...\n...";
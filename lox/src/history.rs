@@ -0,0 +1,93 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Lines entered in the REPL, persisted across sessions.
+///
+/// Lives in `~/.lox_history` unless `LOX_HISTORY` points somewhere else.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    entries: Vec<String>,
+}
+
+impl History {
+    /// Loads the history from its default location, `None` when there is no
+    /// home directory to keep it in.
+    pub fn load() -> Option<History> {
+        Some(History::open(&default_path()?))
+    }
+
+    /// Loads the history kept at `path`. A missing or unreadable file is an
+    /// empty history.
+    pub fn open(path: &Path) -> History {
+        let entries = std::fs::read_to_string(path)
+            .map(|history| history.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        History {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// Appends `line` to the history file. Blank lines and repeats of the
+    /// previous entry are skipped.
+    pub fn push(&mut self, line: &str) -> std::io::Result<()> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+
+        self.entries.push(line.to_string());
+        Ok(())
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("LOX_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".lox_history"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::History;
+
+    #[test]
+    fn push_and_reload() {
+        let path = std::env::temp_dir().join("lox-history-push-and-reload");
+        let _ = std::fs::remove_file(&path);
+
+        let mut history = History::open(&path);
+        assert!(history.entries.is_empty());
+
+        history
+            .push("1 + 2\n")
+            .expect("The temporary dir is writable");
+        history
+            .push("   \n")
+            .expect("The temporary dir is writable");
+        history
+            .push("print 3;\n")
+            .expect("The temporary dir is writable");
+        history
+            .push("print 3;\n")
+            .expect("The temporary dir is writable");
+
+        let history = History::open(&path);
+        assert_eq!(history.entries, ["1 + 2", "print 3;"]);
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("The file was written"),
+            "1 + 2\nprint 3;\n"
+        );
+    }
+}
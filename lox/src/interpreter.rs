@@ -0,0 +1,1126 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{
+    ast::{
+        self, BinaryKind, Expression, ExpressionItem, LogicalKind, Program, Statement, UnaryKind,
+    },
+    span::Span,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Function(Function),
+    Native(Native),
+}
+
+/// A function declared with `fun`, or a lambda if its name is empty. Calls
+/// run its body in a fresh scope on top of the environment it was declared
+/// in, its closure.
+#[derive(Clone)]
+pub struct Function {
+    declaration: Rc<ast::Function>,
+    closure: Rc<RefCell<Environment>>,
+}
+
+/// Only the name, the closure may well hold the function itself.
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.declaration.name.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.declaration, &other.declaration)
+    }
+}
+
+impl Function {
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+/// A function implemented in Rust that every program can call, like
+/// `clock`.
+#[derive(Debug, Clone)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Value,
+}
+
+/// There is a single native function by each name.
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// The native functions defined as globals before a program runs.
+const NATIVES: &[Native] = &[Native {
+    name: "clock",
+    arity: 0,
+    function: clock,
+}];
+
+/// Seconds since the Unix epoch, to time programs with.
+fn clock(_: &[Value]) -> Value {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |duration| duration.as_secs_f64());
+    Value::Number(since_epoch)
+}
+
+/// The variables declared in a scope, looking up the ones it doesn't
+/// declare in the `enclosing` scope.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    /// A scope nested within `enclosing`.
+    pub fn new(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// The value of `name` in the innermost scope that declares it.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match (self.values.get(name), &self.enclosing) {
+            (Some(value), _) => Some(value.clone()),
+            (None, Some(enclosing)) => enclosing.borrow().get(name),
+            (None, None) => None,
+        }
+    }
+
+    /// Sets `name` in the innermost scope that declares it, returning
+    /// whether any does.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        match (self.values.get_mut(name), &self.enclosing) {
+            (Some(slot), _) => {
+                *slot = value;
+                true
+            }
+            (None, Some(enclosing)) => enclosing.borrow_mut().assign(name, value),
+            (None, None) => false,
+        }
+    }
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::Function(function) if function.declaration.name.name.is_empty() => {
+                write!(f, "<fn>")
+            }
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.name),
+            Value::Native(native) => write!(f, "<native fn {}>", native.name),
+        }
+    }
+}
+
+/// How many calls may be running at once. Unoptimized builds take 16 to
+/// 32 KiB of stack per call, depending on how nested the body is.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// The longest string, in bytes, that repeating one with `*` may build.
+/// Anything longer is a runtime error rather than an allocation failure.
+pub const MAX_STRING_LEN: usize = 1 << 30;
+
+#[derive(Debug)]
+pub struct Error {
+    pub span: Span,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    OperandMustBeNumber,
+    OperandsMustBeNumbers,
+    OperandsMustBeNumbersOrStrings,
+    /// A string repeated by a count that is negative or has a fraction.
+    InvalidRepeatCount(f64),
+    /// A string repeated into one longer than [`MAX_STRING_LEN`].
+    RepeatedStringTooLong(f64),
+    UndefinedVariable(String),
+    NotCallable,
+    /// A call nested deeper than [`MAX_CALL_DEPTH`], likely a recursion
+    /// that never ends.
+    StackOverflow,
+    Arity {
+        expected: usize,
+        found: usize,
+    },
+    ReturnOutsideFunction,
+    /// Unwinds a call from its `return` statement, never seen outside
+    /// [`Interpreter::interpret`].
+    Return(Value),
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    /// Unwinds the body of a loop from its `break` statement, like
+    /// [`ErrorKind::Return`].
+    Break,
+    /// Unwinds the body of a loop from its `continue` statement, like
+    /// [`ErrorKind::Return`].
+    Continue,
+    ClassesUnsupported,
+    OnlyInstancesHaveProperties,
+    ThisOutsideClass,
+    SuperOutsideClass,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::OperandMustBeNumber => write!(f, "operand must be a number"),
+            ErrorKind::OperandsMustBeNumbers => write!(f, "operands must be numbers"),
+            ErrorKind::OperandsMustBeNumbersOrStrings => {
+                write!(f, "operands must be two numbers or two strings")
+            }
+            ErrorKind::InvalidRepeatCount(count) => write!(
+                f,
+                "can't repeat a string {count} times, the count must be a non-negative integer"
+            ),
+            ErrorKind::RepeatedStringTooLong(count) => write!(
+                f,
+                "can't repeat a string {count} times, the result would exceed \
+                 {MAX_STRING_LEN} bytes"
+            ),
+            ErrorKind::UndefinedVariable(name) => write!(f, "undefined variable '{name}'"),
+            ErrorKind::NotCallable => write!(f, "can only call functions and classes"),
+            ErrorKind::StackOverflow => {
+                write!(f, "stack overflow, more than {MAX_CALL_DEPTH} nested calls")
+            }
+            ErrorKind::Arity { expected, found } => {
+                write!(f, "expected {expected} arguments but got {found}")
+            }
+            ErrorKind::ReturnOutsideFunction | ErrorKind::Return(_) => {
+                write!(f, "can't return from top-level code")
+            }
+            ErrorKind::BreakOutsideLoop | ErrorKind::Break => {
+                write!(f, "can't use 'break' outside of a loop")
+            }
+            ErrorKind::ContinueOutsideLoop | ErrorKind::Continue => {
+                write!(f, "can't use 'continue' outside of a loop")
+            }
+            ErrorKind::ClassesUnsupported => write!(f, "classes are not supported yet"),
+            ErrorKind::OnlyInstancesHaveProperties => write!(f, "only instances have properties"),
+            ErrorKind::ThisOutsideClass => write!(f, "can't use 'this' outside of a class"),
+            ErrorKind::SuperOutsideClass => write!(f, "can't use 'super' outside of a class"),
+        }
+    }
+}
+
+impl Error {
+    /// Turns a `break` or `continue` that unwound past every loop into the
+    /// error of using it there.
+    fn outside_loop(self) -> Error {
+        let kind = match self.kind {
+            ErrorKind::Break => ErrorKind::BreakOutsideLoop,
+            ErrorKind::Continue => ErrorKind::ContinueOutsideLoop,
+            kind => kind,
+        };
+        Error {
+            span: self.span,
+            kind,
+        }
+    }
+}
+
+type Result<T> = std::prelude::rust_2021::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    /// The scope of the block being executed.
+    environment: Rc<RefCell<Environment>>,
+    /// How many calls are running.
+    depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        let mut globals = Environment::default();
+        for native in NATIVES {
+            globals.define(native.name, Value::Native(native.clone()));
+        }
+
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            depth: 0,
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /// The value of the global variable `name`, if it's declared.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get(name)
+    }
+
+    /// Executes the statements of `program` in order, stopping at the first
+    /// runtime error.
+    pub fn interpret(&mut self, program: &Program) -> Result<()> {
+        program
+            .statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement))
+            .map_err(|err| match err.kind {
+                ErrorKind::Return(_) => Error {
+                    span: err.span,
+                    kind: ErrorKind::ReturnOutsideFunction,
+                },
+                _ => err.outside_loop(),
+            })
+    }
+
+    pub fn execute(&mut self, statement: &Statement) -> Result<()> {
+        match statement {
+            Statement::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            Statement::Print(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| Ok(self.evaluate(value)?.to_string()))
+                    .collect::<Result<Vec<_>>>()?;
+                println!("{}", values.join(" "));
+            }
+            Statement::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(&name.name, value);
+            }
+            Statement::Function(function) => {
+                let value = Value::Function(Function {
+                    declaration: Rc::new(function.clone()),
+                    closure: Rc::clone(&self.environment),
+                });
+                self.environment
+                    .borrow_mut()
+                    .define(&function.name.name, value);
+            }
+            Statement::Class { name, .. } => {
+                return Err(Error {
+                    span: name.span,
+                    kind: ErrorKind::ClassesUnsupported,
+                });
+            }
+            Statement::Break(span) => {
+                return Err(Error {
+                    span: *span,
+                    kind: ErrorKind::Break,
+                });
+            }
+            Statement::Continue(span) => {
+                return Err(Error {
+                    span: *span,
+                    kind: ErrorKind::Continue,
+                });
+            }
+            Statement::Return {
+                keyword_span,
+                value,
+            } => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+                return Err(Error {
+                    span: *keyword_span,
+                    kind: ErrorKind::Return(value),
+                });
+            }
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    match self.execute(body) {
+                        Ok(())
+                        | Err(Error {
+                            kind: ErrorKind::Continue,
+                            ..
+                        }) => {}
+                        Err(Error {
+                            kind: ErrorKind::Break,
+                            ..
+                        }) => break,
+                        Err(err) => return Err(err),
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
+                }
+            }
+            Statement::Block(statements) => {
+                let environment = Environment::new(Rc::clone(&self.environment));
+                self.execute_block(statements, environment)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expression) -> Result<Value> {
+        match &expr.item {
+            ExpressionItem::Number(n) => Ok(Value::Number(*n)),
+            ExpressionItem::String(s) => Ok(Value::String(s.clone())),
+            ExpressionItem::Bool(b) => Ok(Value::Bool(*b)),
+            ExpressionItem::Nil => Ok(Value::Nil),
+            ExpressionItem::Grouping(expr) => self.evaluate(expr),
+            ExpressionItem::Variable(name) => {
+                self.environment.borrow().get(name).ok_or_else(|| Error {
+                    span: expr.span,
+                    kind: ErrorKind::UndefinedVariable(name.clone()),
+                })
+            }
+            ExpressionItem::Unary(operand, kind) => {
+                let value = self.evaluate(operand)?;
+                match kind {
+                    UnaryKind::Bang => Ok(Value::Bool(!value.is_truthy())),
+                    UnaryKind::Minus => match value {
+                        Value::Number(n) => Ok(Value::Number(-n)),
+                        // The operator is the one character the node starts with
+                        _ => Err(Error {
+                            span: Span::from(expr.span.start..expr.span.start + 1),
+                            kind: ErrorKind::OperandMustBeNumber,
+                        }),
+                    },
+                }
+            }
+            ExpressionItem::Assign { name, value } => {
+                let value = self.evaluate(value)?;
+                if !self
+                    .environment
+                    .borrow_mut()
+                    .assign(&name.name, value.clone())
+                {
+                    return Err(Error {
+                        span: name.span,
+                        kind: ErrorKind::UndefinedVariable(name.name.clone()),
+                    });
+                }
+                Ok(value)
+            }
+            ExpressionItem::Call {
+                callee,
+                paren_span,
+                args,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let arity = |expected: usize| {
+                    if args.len() == expected {
+                        return Ok(());
+                    }
+                    Err(Error {
+                        span: *paren_span,
+                        kind: ErrorKind::Arity {
+                            expected,
+                            found: args.len(),
+                        },
+                    })
+                };
+
+                match callee {
+                    Value::Function(function) => {
+                        arity(function.arity())?;
+                        self.call(expr.span, &function, args)
+                    }
+                    Value::Native(native) => {
+                        arity(native.arity)?;
+                        Ok((native.function)(&args))
+                    }
+                    _ => Err(Error {
+                        span: *paren_span,
+                        kind: ErrorKind::NotCallable,
+                    }),
+                }
+            }
+            ExpressionItem::Comma(exprs) => {
+                let mut value = Value::Nil;
+                for expr in exprs {
+                    value = self.evaluate(expr)?;
+                }
+                Ok(value)
+            }
+            // Classes don't run yet, so there is never a class around
+            ExpressionItem::This => Err(Error {
+                span: expr.span,
+                kind: ErrorKind::ThisOutsideClass,
+            }),
+            ExpressionItem::Super { keyword_span, .. } => Err(Error {
+                span: *keyword_span,
+                kind: ErrorKind::SuperOutsideClass,
+            }),
+            // There are no instances to hold properties yet
+            ExpressionItem::Get { object, name } => {
+                self.evaluate(object)?;
+                Err(Error {
+                    span: name.span,
+                    kind: ErrorKind::OnlyInstancesHaveProperties,
+                })
+            }
+            ExpressionItem::Set { object, name, .. } => {
+                self.evaluate(object)?;
+                Err(Error {
+                    span: name.span,
+                    kind: ErrorKind::OnlyInstancesHaveProperties,
+                })
+            }
+            ExpressionItem::Lambda { params, body } => Ok(Value::Function(Function {
+                declaration: Rc::new(ast::Function {
+                    name: ast::Identifier {
+                        span: expr.span,
+                        name: String::new(),
+                    },
+                    params: params.clone(),
+                    body: body.clone(),
+                }),
+                closure: Rc::clone(&self.environment),
+            })),
+            ExpressionItem::Binary(lhs, rhs, kind, operator) => {
+                self.binary(*operator, lhs, rhs, kind)
+            }
+            ExpressionItem::Logical(lhs, rhs, kind) => {
+                let lhs = self.evaluate(lhs)?;
+                match kind {
+                    LogicalKind::And if !lhs.is_truthy() => Ok(lhs),
+                    LogicalKind::Or if lhs.is_truthy() => Ok(lhs),
+                    LogicalKind::And | LogicalKind::Or => self.evaluate(rhs),
+                }
+            }
+        }
+    }
+
+    /// Runs the body of `function` with its parameters bound to `args` in
+    /// a scope within its closure, the value of its `return` being the
+    /// result. `span` is the call, blamed when there are too many.
+    fn call(&mut self, span: Span, function: &Function, args: Vec<Value>) -> Result<Value> {
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(Error {
+                span,
+                kind: ErrorKind::StackOverflow,
+            });
+        }
+
+        let declaration = &function.declaration;
+        let mut environment = Environment::new(Rc::clone(&function.closure));
+        for (param, arg) in declaration.params.iter().zip(args) {
+            environment.define(&param.name, arg);
+        }
+
+        self.depth += 1;
+        let res = self.execute_block(&declaration.body, environment);
+        self.depth -= 1;
+
+        match res {
+            Ok(()) => Ok(Value::Nil),
+            Err(Error {
+                kind: ErrorKind::Return(value),
+                ..
+            }) => Ok(value),
+            Err(err) => Err(err.outside_loop()),
+        }
+    }
+
+    /// Executes `statements` in `environment`, going back to the current
+    /// one afterwards even if they fail.
+    fn execute_block(&mut self, statements: &[Statement], environment: Environment) -> Result<()> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let res = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        res
+    }
+
+    /// Evaluates `lhs kind rhs`, the operand errors point at the operator,
+    /// at `span`.
+    fn binary(
+        &mut self,
+        span: Span,
+        lhs: &Expression,
+        rhs: &Expression,
+        kind: &BinaryKind,
+    ) -> Result<Value> {
+        let lhs = self.evaluate(lhs)?;
+        let rhs = self.evaluate(rhs)?;
+
+        let numbers = || match (&lhs, &rhs) {
+            (Value::Number(a), Value::Number(b)) => Ok((*a, *b)),
+            _ => Err(Error {
+                span,
+                kind: ErrorKind::OperandsMustBeNumbers,
+            }),
+        };
+        // Bitwise operators work on the numbers truncated to integers
+        let integers = || numbers().map(|(a, b)| (a as i64, b as i64));
+
+        Ok(match kind {
+            BinaryKind::Plus => match (&lhs, &rhs) {
+                (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                (Value::String(a), Value::String(b)) => Value::String(format!("{a}{b}")),
+                _ => {
+                    return Err(Error {
+                        span,
+                        kind: ErrorKind::OperandsMustBeNumbersOrStrings,
+                    })
+                }
+            },
+            BinaryKind::Minus => numbers().map(|(a, b)| Value::Number(a - b))?,
+            BinaryKind::Star => match (&lhs, &rhs) {
+                (Value::String(s), Value::Number(count)) => {
+                    if *count < 0.0 || count.fract() != 0.0 {
+                        return Err(Error {
+                            span,
+                            kind: ErrorKind::InvalidRepeatCount(*count),
+                        });
+                    }
+                    if s.len() as f64 * count > MAX_STRING_LEN as f64 {
+                        return Err(Error {
+                            span,
+                            kind: ErrorKind::RepeatedStringTooLong(*count),
+                        });
+                    }
+                    Value::String(s.repeat(*count as usize))
+                }
+                _ => numbers().map(|(a, b)| Value::Number(a * b))?,
+            },
+            BinaryKind::Slash => numbers().map(|(a, b)| Value::Number(a / b))?,
+            BinaryKind::Mod => numbers().map(|(a, b)| Value::Number(a % b))?,
+            BinaryKind::Pow => numbers().map(|(a, b)| Value::Number(a.powf(b)))?,
+            BinaryKind::Greater => numbers().map(|(a, b)| Value::Bool(a > b))?,
+            BinaryKind::GreaterEqual => numbers().map(|(a, b)| Value::Bool(a >= b))?,
+            BinaryKind::Less => numbers().map(|(a, b)| Value::Bool(a < b))?,
+            BinaryKind::LessEqual => numbers().map(|(a, b)| Value::Bool(a <= b))?,
+            BinaryKind::EqualEqual => Value::Bool(lhs == rhs),
+            BinaryKind::BangEqual => Value::Bool(lhs != rhs),
+            BinaryKind::BitAnd => integers().map(|(a, b)| Value::Number((a & b) as f64))?,
+            BinaryKind::BitOr => integers().map(|(a, b)| Value::Number((a | b) as f64))?,
+            BinaryKind::BitXor => integers().map(|(a, b)| Value::Number((a ^ b) as f64))?,
+            BinaryKind::Shl => {
+                integers().map(|(a, b)| Value::Number(a.wrapping_shl(b as u32) as f64))?
+            }
+            BinaryKind::Shr => {
+                integers().map(|(a, b)| Value::Number(a.wrapping_shr(b as u32) as f64))?
+            }
+            BinaryKind::Equal => unreachable!("Assignment is never parsed as a binary expression"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{ErrorKind, Interpreter, Value};
+    use crate::{parser::Parser, scanner::Scanner, span::Span};
+
+    fn eval(source: &str) -> super::Result<Value> {
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+        let expr = Parser::new(&tokens, source)
+            .parse()
+            .expect("It's guaranteed to parse");
+
+        Interpreter::new().evaluate(&expr)
+    }
+
+    fn interpret(source: &str) -> (Interpreter, super::Result<()>) {
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+        let program = Parser::new(&tokens, source)
+            .parse_program()
+            .expect("It's guaranteed to parse");
+
+        let mut interpreter = Interpreter::new();
+        let res = interpreter.interpret(&program);
+        (interpreter, res)
+    }
+
+    #[test]
+    fn variables() {
+        let (interpreter, res) = interpret("var a = 1;\nvar b = a + 2;\nvar c;");
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("b"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.global("c"), Some(Value::Nil));
+    }
+
+    #[test]
+    fn assignment() {
+        let (interpreter, res) = interpret("var a;\nvar b;\nvar c = a = b = 2;");
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("a"), Some(Value::Number(2.0)));
+        assert_eq!(interpreter.global("c"), Some(Value::Number(2.0)));
+
+        let (_, res) = interpret("d = 1;");
+        let err = res.expect_err("d is not declared");
+        assert_eq!(err.span, Span::from(0..1));
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let (interpreter, res) = interpret(
+            "var a = 7;\na += 3;\na -= 1;\na *= 2;\na /= 3;\na %= 4;\nvar s = \"a\";\ns += \"b\";",
+        );
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("a"), Some(Value::Number(2.0)));
+        assert_eq!(
+            interpreter.global("s"),
+            Some(Value::String("ab".to_string()))
+        );
+
+        let (_, res) = interpret("var n;\nn += 1;");
+        let err = res.expect_err("nil can't be added to");
+        assert_eq!(err.span, Span::from(9..11));
+    }
+
+    #[test]
+    fn block_scopes() {
+        let (interpreter, res) = interpret("var a = 1;\nvar b;\n{ var a = 2; b = a; }\nvar c = a;");
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("b"), Some(Value::Number(2.0)));
+        assert_eq!(interpreter.global("c"), Some(Value::Number(1.0)));
+        assert!(Rc::ptr_eq(&interpreter.environment, &interpreter.globals));
+
+        let (_, res) = interpret("{ var a = 1; }\nprint a;");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn loops() {
+        let (interpreter, res) = interpret(
+            "var sum = 0;\nfor (var i = 0; i < 4; i = i + 1) sum = sum + i;\nvar n = 0;\nwhile (n < 5) n = n + 2;",
+        );
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("sum"), Some(Value::Number(6.0)));
+        assert_eq!(interpreter.global("n"), Some(Value::Number(6.0)));
+        assert_eq!(interpreter.global("i"), None);
+    }
+
+    #[test]
+    fn call_non_callable() {
+        let err = eval("\"not a function\"(1)").expect_err("Strings can't be called");
+
+        assert_eq!(err.span, Span::from(18..19));
+        assert_eq!(err.kind, ErrorKind::NotCallable);
+    }
+
+    #[test]
+    fn endless_recursion_is_an_error() {
+        // Test threads only get 2 MiB, too little for `MAX_CALL_DEPTH` calls
+        let deep = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| {
+                let (interpreter, res) = interpret("fun f() { f(); }\nf();");
+                let err = res.expect_err("It never returns");
+                assert_eq!(err.kind, ErrorKind::StackOverflow);
+                assert_eq!(err.span, Span::from(10..13));
+                assert!(Rc::ptr_eq(&interpreter.environment, &interpreter.globals));
+
+                let (interpreter, res) =
+                    interpret("var n = 0;\nfun f() { n = n + 1; return n < 1000 and f(); }\nf();");
+                res.expect("It's deep, not endless");
+                assert_eq!(interpreter.global("n"), Some(Value::Number(1000.0)));
+            })
+            .expect("The thread can be spawned");
+
+        deep.join().expect("The stack is large enough");
+    }
+
+    #[test]
+    fn recursive_function() {
+        let (interpreter, res) = interpret(
+            "fun fib(n) {\n  return n < 2 and n or fib(n - 1) + fib(n - 2);\n}\nvar a = fib(10);",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("a"), Some(Value::Number(55.0)));
+        assert!(Rc::ptr_eq(&interpreter.environment, &interpreter.globals));
+    }
+
+    #[test]
+    fn function_calls() {
+        let (interpreter, res) = interpret(
+            "var calls = 0;\nfun count(by) { calls = calls + by; }\nvar a = count(2);\ncount(3);",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("a"), Some(Value::Nil));
+        assert_eq!(interpreter.global("calls"), Some(Value::Number(5.0)));
+        assert_eq!(
+            interpreter
+                .global("count")
+                .expect("It's declared")
+                .to_string(),
+            "<fn count>"
+        );
+
+        // Parameters and locals don't leak out of the call
+        let (_, res) = interpret("fun f(a) { var b = a; }\nf(1);\nprint a;");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn closures() {
+        let (interpreter, res) = interpret(
+            "fun makeCounter() {\n  var i = 0;\n  fun count() { i = i + 1; return i; }\n  return count;\n}\nvar counter = makeCounter();\nvar a = counter();\nvar b = counter();\nvar c = makeCounter()();",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("a"), Some(Value::Number(1.0)));
+        assert_eq!(interpreter.global("b"), Some(Value::Number(2.0)));
+        // Each call makes a counter of its own
+        assert_eq!(interpreter.global("c"), Some(Value::Number(1.0)));
+        assert_eq!(interpreter.global("i"), None);
+
+        // Closures see the variables they close over, not the caller's
+        let (interpreter, res) = interpret(
+            "var a = \"global\";\nvar b;\n{ var a = \"block\"; fun show() { return a; }\n  { var a = \"inner\"; b = show(); } }\nfun outer() { var a = \"outer\"; return show2(); }\nfun show2() { return a; }\nvar c = outer();",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(
+            interpreter.global("b"),
+            Some(Value::String("block".to_string()))
+        );
+        assert_eq!(
+            interpreter.global("c"),
+            Some(Value::String("global".to_string()))
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        let (interpreter, res) = interpret(
+            "var add = fun (a, b) { return a + b; };\nvar a = add(1, 2);\nvar b = fun (x) { return x * 2; }(4);\nfun twice(f, x) { return f(f(x)); }\nvar c = twice(fun (n) { return n + 3; }, 1);",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("a"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.global("b"), Some(Value::Number(8.0)));
+        assert_eq!(interpreter.global("c"), Some(Value::Number(7.0)));
+        assert_eq!(
+            interpreter.global("add").map(|add| add.to_string()),
+            Some("<fn>".to_string())
+        );
+    }
+
+    #[test]
+    fn arity_mismatch() {
+        let (_, res) = interpret("fun f(a, b) {}\nf(1);");
+        let err = res.expect_err("f takes two arguments");
+
+        assert_eq!(err.span, Span::from(18..19));
+        assert_eq!(
+            err.kind,
+            ErrorKind::Arity {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(err.kind.to_string(), "expected 2 arguments but got 1");
+    }
+
+    #[test]
+    fn clock() {
+        let Ok(Value::Number(seconds)) = eval("clock()") else {
+            panic!("clock() returns a number");
+        };
+        assert!(seconds > 0.0);
+        assert_eq!(
+            eval("clock").map(|clock| clock.to_string()).ok(),
+            Some("<native fn clock>".to_string())
+        );
+
+        let err = eval("clock(1)").expect_err("clock takes no arguments");
+        assert_eq!(err.span, Span::from(7..8));
+        assert_eq!(
+            err.kind,
+            ErrorKind::Arity {
+                expected: 0,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn return_outside_function() {
+        let (_, res) = interpret("return 1;");
+        let err = res.expect_err("There is no function to return from");
+
+        assert_eq!(err.span, Span::from(0..6));
+        assert_eq!(err.kind, ErrorKind::ReturnOutsideFunction);
+    }
+
+    #[test]
+    fn break_exits_early() {
+        let (interpreter, res) =
+            interpret("var a = 0;\nwhile (true) { a = a + 1; break; a = 10; }\nvar b = a;");
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("b"), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn continue_skips_to_next_iteration() {
+        let (interpreter, res) = interpret(
+            "var n = 0;\nfor (var i = 0; i < 3; i = i + 1) { n = n + 1; continue; n = 10; }",
+        );
+
+        assert!(res.is_ok());
+        assert_eq!(interpreter.global("n"), Some(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn loop_control_outside_loop() {
+        let (_, res) = interpret("print 1;\nbreak;");
+        let err = res.expect_err("There is no loop to break out of");
+        assert_eq!(err.span, Span::from(9..14));
+        assert_eq!(err.kind, ErrorKind::BreakOutsideLoop);
+
+        // A function body is not part of the loop it's called from
+        let (_, res) = interpret("fun f() { continue; }\nwhile (true) f();");
+        let err = res.expect_err("There is no loop in f");
+        assert_eq!(err.span, Span::from(10..18));
+        assert_eq!(err.kind, ErrorKind::ContinueOutsideLoop);
+        assert_eq!(
+            err.kind.to_string(),
+            "can't use 'continue' outside of a loop"
+        );
+    }
+
+    #[test]
+    fn undefined_variable() {
+        let (_, res) = interpret("var a = b;");
+        let err = res.expect_err("b is not defined");
+
+        assert_eq!(err.span, Span::from(8..9));
+        assert_eq!(err.kind, ErrorKind::UndefinedVariable("b".to_string()));
+        assert_eq!(err.kind.to_string(), "undefined variable 'b'");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("1 + 2 * 3").ok(), Some(Value::Number(7.0)));
+        assert_eq!(eval("2 ** 3 ** 2").ok(), Some(Value::Number(512.0)));
+        assert_eq!(eval("7 - 2 - 1").ok(), Some(Value::Number(4.0)));
+        assert_eq!(eval("6 | 1 ^ 3 & 2 << 1").ok(), Some(Value::Number(7.0)));
+    }
+
+    #[test]
+    fn power_binds_tighter_than_negation() {
+        assert_eq!(eval("-2 ** 2").ok(), Some(Value::Number(-4.0)));
+        assert_eq!(eval("(-2) ** 2").ok(), Some(Value::Number(4.0)));
+        assert_eq!(eval("2 ** -1").ok(), Some(Value::Number(0.5)));
+        assert_eq!(eval("2 * 3 ** 2").ok(), Some(Value::Number(18.0)));
+
+        let err = eval("2 ** nil").expect_err("Only numbers have powers");
+        assert_eq!(err.kind, ErrorKind::OperandsMustBeNumbers);
+    }
+
+    #[test]
+    fn nested_unary_operators() {
+        assert_eq!(eval("--5").ok(), Some(Value::Number(5.0)));
+        assert_eq!(eval("---5").ok(), Some(Value::Number(-5.0)));
+        assert_eq!(eval("!!false").ok(), Some(Value::Bool(false)));
+        // Not dropped, `!!` turns any value into its truthiness
+        assert_eq!(eval("!!nil").ok(), Some(Value::Bool(false)));
+        assert_eq!(eval("!!\"\"").ok(), Some(Value::Bool(true)));
+
+        let err = eval("--\"a\"").expect_err("The inner `-` fails");
+        assert_eq!(err.span, Span::from(1..2));
+    }
+
+    #[test]
+    fn comparison_and_equality() {
+        assert_eq!(eval("1 < 2 == true").ok(), Some(Value::Bool(true)));
+        assert_eq!(eval("nil == false").ok(), Some(Value::Bool(false)));
+        assert_eq!(eval("\"a\" != \"a\"").ok(), Some(Value::Bool(false)));
+        assert_eq!(eval("!nil").ok(), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn logical_short_circuit() {
+        assert_eq!(
+            eval("nil or \"yes\"").ok(),
+            Some(Value::String("yes".to_string()))
+        );
+        assert_eq!(eval("1 and 2").ok(), Some(Value::Number(2.0)));
+        assert_eq!(eval("false and missing").ok(), Some(Value::Bool(false)));
+        assert_eq!(eval("true or missing").ok(), Some(Value::Bool(true)));
+        assert!(eval("true and missing").is_err());
+    }
+
+    #[test]
+    fn short_circuit_skips_side_effects() {
+        let (interpreter, res) = interpret(
+            "var calls = 0;\nfun touch(v) { calls = calls + 1; return v; }\nvar a = false and touch(1);\nvar b = true or touch(2);\nvar c = nil and (calls = 10);\nvar d = \"x\" or (calls = 20);",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("calls"), Some(Value::Number(0.0)));
+        // The operand itself, not a bool
+        assert_eq!(interpreter.global("a"), Some(Value::Bool(false)));
+        assert_eq!(interpreter.global("b"), Some(Value::Bool(true)));
+        assert_eq!(interpreter.global("c"), Some(Value::Nil));
+        assert_eq!(
+            interpreter.global("d"),
+            Some(Value::String("x".to_string()))
+        );
+
+        let (interpreter, res) = interpret(
+            "var calls = 0;\nfun touch(v) { calls = calls + 1; return v; }\nvar a = true and touch(nil);\nvar b = false or touch(2);",
+        );
+
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("calls"), Some(Value::Number(2.0)));
+        assert_eq!(interpreter.global("a"), Some(Value::Nil));
+        assert_eq!(interpreter.global("b"), Some(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn comma_results_in_last() {
+        let (interpreter, res) = interpret("var a; var b; var c = (a = 1, b = 2, a + b);");
+        res.expect("It's valid");
+        assert_eq!(interpreter.global("a"), Some(Value::Number(1.0)));
+        assert_eq!(interpreter.global("c"), Some(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn string_concatenation() {
+        assert_eq!(
+            eval("\"lo\" + \"x\"").ok(),
+            Some(Value::String("lox".to_string()))
+        );
+    }
+
+    #[test]
+    fn string_repetition() {
+        assert_eq!(
+            eval("\"ab\" * 3").ok(),
+            Some(Value::String("ababab".to_string()))
+        );
+        assert_eq!(eval("\"ab\" * 0").ok(), Some(Value::String(String::new())));
+        assert_eq!(
+            eval("\"ab\" * 3 == \"ababab\"").ok(),
+            Some(Value::Bool(true))
+        );
+
+        let err = eval("\"ab\" * -1").expect_err("Can't repeat a negative count");
+        assert_eq!(err.kind, ErrorKind::InvalidRepeatCount(-1.0));
+        assert_eq!(err.span, Span::from(5..6));
+        assert_eq!(
+            err.kind.to_string(),
+            "can't repeat a string -1 times, the count must be a non-negative integer"
+        );
+
+        let err = eval("\"ab\" * 1.5").expect_err("Can't repeat a fractional count");
+        assert_eq!(err.kind, ErrorKind::InvalidRepeatCount(1.5));
+
+        let err = eval("\"ab\" * 1e300").expect_err("The string would be too long");
+        assert_eq!(err.kind, ErrorKind::RepeatedStringTooLong(1e300));
+        assert_eq!(err.span, Span::from(5..6));
+        // Nothing to allocate however many times it repeats
+        assert_eq!(
+            eval("\"\" * 1e300").ok(),
+            Some(Value::String(String::new()))
+        );
+
+        // The string goes first
+        let err = eval("3 * \"ab\"").expect_err("Only strings are repeated");
+        assert_eq!(err.kind, ErrorKind::OperandsMustBeNumbers);
+    }
+
+    #[test]
+    fn type_errors() {
+        let err = eval("1 + \"a\"").expect_err("Can't add a number and a string");
+        assert_eq!(err.kind, ErrorKind::OperandsMustBeNumbersOrStrings);
+        assert_eq!(err.span, Span::from(2..3));
+
+        let err = eval("true < 1").expect_err("Can't compare booleans");
+        assert_eq!(err.kind, ErrorKind::OperandsMustBeNumbers);
+
+        let err = eval("-nil").expect_err("Can't negate nil");
+        assert_eq!(err.kind, ErrorKind::OperandMustBeNumber);
+    }
+
+    #[test]
+    fn operator_errors_span_the_operator() {
+        let err = eval("-\"a\"").expect_err("Can't negate a string");
+        assert_eq!(err.span, Span::from(0..1));
+
+        let err = eval("1 + - -\"a\"").expect_err("Can't negate a string");
+        assert_eq!(err.span, Span::from(6..7));
+
+        let err = eval("(1) * nil").expect_err("Can't multiply nil");
+        assert_eq!(err.span, Span::from(4..5));
+
+        let err = eval("\"a\" + 1 ** nil").expect_err("Can't raise to nil");
+        assert_eq!(err.span, Span::from(8..10));
+
+        let err = eval("1 + 2 << \"a\"").expect_err("Can't shift by a string");
+        assert_eq!(err.span, Span::from(6..8));
+    }
+
+    #[test]
+    fn display_values() {
+        assert_eq!(Value::Number(7.0).to_string(), "7");
+        assert_eq!(Value::Number(2.5).to_string(), "2.5");
+        assert_eq!(Value::Nil.to_string(), "nil");
+        assert_eq!(Value::String("hi".to_string()).to_string(), "hi");
+    }
+}
@@ -0,0 +1,14 @@
+#![deny(clippy::unwrap_used)]
+#![feature(let_chains)]
+
+//! A Lox scanner, parser and tree-walk interpreter. The `lox` binary is a
+//! thin command line interface over this library.
+
+pub mod ast;
+pub mod diag;
+pub mod interpreter;
+pub mod optimize;
+pub mod parser;
+pub mod scanner;
+pub mod span;
+pub mod visit;
@@ -1,26 +1,41 @@
 #![deny(clippy::unwrap_used)]
 #![feature(let_chains)]
 
-mod ast;
-mod diag;
-mod parser;
-mod scanner;
-mod span;
+mod history;
 
 use std::env::args;
 use std::fs::OpenOptions;
-use std::io::{stdin, Read, Write};
-use std::ops::Not;
+use std::io::{stdin, IsTerminal, Read, Write};
 use std::path::Path;
 use std::process::ExitCode;
 use std::str::{self};
 
-use diag::Diagnostic;
-use parser::Parser;
+use lox::diag::{self, Diagnostic, DiagnosticSink};
+use lox::parser::{self, Parser};
+use lox::{ast, interpreter, scanner, span};
+use owo_colors::{OwoColorize, Stream};
 
+/// The commands the REPL takes instead of an expression.
+const REPL_HELP: &str = "\
+:ast     toggle printing the parsed tree instead of the value
+:tokens  print the tokens of the next line
+:quit    exit the REPL";
+
+/// Reads expressions from stdin, continuing with a `... ` prompt while the
+/// input so far is incomplete. Lines typed in a terminal are saved to the
+/// [`history::History`]. A line starting with `:` is a command, see
+/// [`REPL_HELP`].
 fn editline(buf: &mut String) {
+    let mut history = stdin().is_terminal().then(history::History::load).flatten();
+    let mut prompt = "> ";
+    let mut emit = Emit::Value;
+    // Only for the next line
+    let mut emit_once = None;
+    // Shared by every line, so variables outlive the line defining them
+    let mut interpreter = interpreter::Interpreter::new();
+
     while let Ok(n) = {
-        print!("> ");
+        print!("{prompt}");
         std::io::stdout()
             .flush()
             .expect("We are not expecting flush to fail");
@@ -29,74 +44,373 @@ fn editline(buf: &mut String) {
         if n == 0 {
             break;
         }
-        if let Err(err) = run(Path::new("REPL"), buf) {
-            for error in err {
-                println!("{error}");
+        if let Some(saved) = &mut history
+            && let Err(err) = saved.push(&buf[buf.len() - n..])
+        {
+            eprintln!("Failed to save the REPL history, not saving it anymore: {err}");
+            history = None;
+        }
+        if buf.trim().is_empty() {
+            buf.clear();
+            continue;
+        }
+        if let Some(command) = buf.trim().strip_prefix(':') {
+            match command {
+                "ast" => {
+                    emit = match emit {
+                        Emit::Ast => Emit::Value,
+                        _ => Emit::Ast,
+                    };
+                }
+                "tokens" => emit_once = Some(Emit::Tokens),
+                "quit" => break,
+                _ => println!("{REPL_HELP}"),
+            }
+            buf.clear();
+            continue;
+        }
+
+        let mut sink = DiagnosticSink::new(Stream::Stdout);
+        let res = run_expression(
+            Path::new("REPL"),
+            buf,
+            emit_once.unwrap_or(emit),
+            &mut interpreter,
+            &mut sink,
+        );
+        match res {
+            Err(err) if is_incomplete(&err) => {
+                prompt = "... ";
+                continue;
             }
-        };
+            Err(err) => {
+                for error in err {
+                    println!("{error}");
+                }
+            }
+            Ok(()) => {}
+        }
+        let warnings: Vec<_> = sink.drain().collect();
+        for warning in &warnings {
+            println!("{}", sink.render(warning));
+        }
+
+        prompt = "> ";
+        emit_once = None;
         buf.clear();
     }
 }
 
-fn run<'src>(path: &'src Path, source: &'src str) -> Result<(), Vec<CompError<'src>>> {
-    let scanner = scanner::Scanner::new(source);
+/// Whether parsing failed only because the input ended too early.
+fn is_incomplete(errors: &[CompError<'_>]) -> bool {
+    matches!(errors, [CompError::ParserError(ParserError { error, .. })] if error.is_eof())
+}
 
-    let tokens: Vec<_> = scanner
+/// What `run` prints for the source it is given.
+#[derive(Debug, Clone, Copy)]
+enum Emit {
+    /// Print the parsed tree in its S-expression form.
+    Ast,
+    /// Run the program, or evaluate a single expression and print its value.
+    Value,
+    /// Only scan the source and print every token, trivia included.
+    Tokens,
+    #[cfg(feature = "serde")]
+    AstJson,
+    /// Only scan and parse, so the diagnostics are the only output.
+    #[cfg(feature = "serde")]
+    Check,
+}
+
+/// Runs a whole program, a sequence of statements. Warnings go straight to
+/// `sink`, they don't stop the program from running.
+fn run<'src>(
+    path: &'src Path,
+    source: &'src str,
+    emit: Emit,
+    sink: &mut DiagnosticSink<'src>,
+) -> Result<(), Vec<CompError<'src>>> {
+    if let Emit::Tokens = emit {
+        return dump_tokens(path, source);
+    }
+
+    let (tokens, errors) = scan(path, source);
+    let mut parser = Parser::new(&tokens, source);
+    let program = parser.parse_program();
+    push_warnings(path, source, parser.warnings(), sink);
+
+    let mut interpreter = interpreter::Interpreter::new();
+    emit_program(path, source, program, errors, emit, &mut interpreter)
+}
+
+fn push_warnings<'src>(
+    path: &'src Path,
+    source: &'src str,
+    warnings: &[parser::Warning],
+    sink: &mut DiagnosticSink<'src>,
+) {
+    for warning in warnings {
+        sink.push(
+            warning.labels().into_iter().fold(
+                Diagnostic::new(source, path, warning.span, warning.kind.to_string())
+                    .with_severity(diag::Severity::Warning),
+                |diag, (span, msg)| diag.with_label(span, msg),
+            ),
+        );
+    }
+}
+
+/// Reports the errors of a parsed program, or emits it if there are none.
+fn emit_program<'src>(
+    path: &'src Path,
+    source: &'src str,
+    program: Result<ast::Program, Vec<parser::Error>>,
+    mut errors: Vec<CompError<'src>>,
+    emit: Emit,
+    interpreter: &mut interpreter::Interpreter,
+) -> Result<(), Vec<CompError<'src>>> {
+    let program = match program {
+        Ok(program) if errors.is_empty() => program,
+        Ok(_) => return Err(errors),
+        Err(parse_errors) => {
+            errors.extend(
+                parse_errors
+                    .into_iter()
+                    .map(|error| parser_error(path, source, error)),
+            );
+            errors.sort_by_key(|error| error.span().start);
+            return Err(errors);
+        }
+    };
+
+    match emit {
+        Emit::Ast => print!("{}", ast::print_program(&program)),
+        Emit::Value => interpreter
+            .interpret(&program)
+            .map_err(|error| vec![runtime_error(path, source, error)])?,
+        Emit::Tokens => unreachable!("Tokens are dumped before parsing"),
+        #[cfg(feature = "serde")]
+        Emit::AstJson => println!("{}", ast::to_json_pretty(&program)),
+        #[cfg(feature = "serde")]
+        Emit::Check => {}
+    }
+
+    Ok(())
+}
+
+/// Runs a single expression, as typed in the REPL or given to `-e`, and
+/// prints its value. Anything else is run as a sequence of statements.
+/// Either way it runs in `interpreter`, keeping the variables defined
+/// before. Warnings go to `sink`.
+fn run_expression<'src>(
+    path: &'src Path,
+    source: &'src str,
+    emit: Emit,
+    interpreter: &mut interpreter::Interpreter,
+    sink: &mut DiagnosticSink<'src>,
+) -> Result<(), Vec<CompError<'src>>> {
+    if let Emit::Tokens = emit {
+        return dump_tokens(path, source);
+    }
+
+    let (tokens, errors) = scan(path, source);
+    let mut parser = Parser::new(&tokens, source);
+    let expr = parser.parse_expression();
+    push_warnings(path, source, parser.warnings(), sink);
+    let expr = match expr {
+        Ok(expr) if errors.is_empty() => expr,
+        Ok(_) => return Err(errors),
+        // Not a lone expression, so statements like `var x = 1;`
+        Err(_) => {
+            let program = parser.parse_program();
+            push_warnings(path, source, parser.warnings(), sink);
+            return emit_program(path, source, program, errors, emit, interpreter);
+        }
+    };
+
+    match emit {
+        Emit::Ast => println!("{}", ast::print(&expr)),
+        Emit::Value => match interpreter.evaluate(&expr) {
+            Ok(value) => println!("{value}"),
+            Err(error) => return Err(vec![runtime_error(path, source, error)]),
+        },
+        Emit::Tokens => unreachable!("Tokens are dumped before parsing"),
+        #[cfg(feature = "serde")]
+        Emit::AstJson => println!(
+            "{}",
+            serde_json::to_string_pretty(&expr).expect("The AST is serializable")
+        ),
+        #[cfg(feature = "serde")]
+        Emit::Check => {}
+    }
+
+    Ok(())
+}
+
+/// Scans the significant tokens of `source`, collecting the scanner errors
+/// on the side.
+fn scan<'src>(path: &'src Path, source: &'src str) -> (Vec<scanner::Token>, Vec<CompError<'src>>) {
+    let (tokens, errors) = scanner::Scanner::new(source).tokenize();
+    let errors = errors
         .into_iter()
-        .filter_map(|token| match token {
-            Err(err) => {
-                Diagnostic::new(
-                    source,
-                    path,
-                    err.span,
-                    format!(
-                        "Scanner error with token {:?}: {err:?}",
-                        &source[err.span.range()]
-                    ),
-                )
-                .err();
-                None
-            }
-            Ok(token) => matches!(
-                token.tipo,
-                scanner::TokenKind::Eof
-                    | scanner::TokenKind::Whitespace
-                    | scanner::TokenKind::CommentLine
-            )
-            .not()
-            .then_some(token),
+        .map(|error| {
+            CompError::ScannerError(ScannerError {
+                path,
+                invalid_token: &source[error.span.range()],
+                error,
+                source,
+            })
         })
         .collect();
 
-    let mut parser = Parser::new(path, &tokens, source);
+    (tokens, errors)
+}
+
+fn parser_error<'src>(
+    path: &'src Path,
+    source: &'src str,
+    error: parser::Error,
+) -> CompError<'src> {
+    CompError::ParserError(ParserError {
+        path,
+        error,
+        source,
+    })
+}
+
+fn runtime_error<'src>(
+    path: &'src Path,
+    source: &'src str,
+    error: interpreter::Error,
+) -> CompError<'src> {
+    CompError::RuntimeError(RuntimeError {
+        path,
+        error,
+        source,
+    })
+}
 
-    let res = parser.parse();
+/// Prints one token per line as `KIND  span  "lexeme"`, marking whitespace
+/// and comments with a `~`.
+fn dump_tokens<'src>(path: &'src Path, source: &'src str) -> Result<(), Vec<CompError<'src>>> {
+    let (tokens, errors) = scanner::Scanner::new(source).scan_all();
 
-    match res {
-        Ok(res) => println!("{res:#?}"),
-        Err(err) => Diagnostic::new(
-            source,
-            path,
-            err.span,
-            format!("Error while parsing: {err:?}"),
-        )
-        .err(),
+    for token in tokens {
+        let trivia = token.tipo.is_trivia();
+        let line = format!(
+            "{marker} {kind:<14} {span:<10} {lexeme:?}",
+            marker = if trivia { '~' } else { ' ' },
+            kind = format!("{:?}", token.tipo),
+            span = format!("{}..{}", token.span.start, token.span.end),
+            lexeme = token.lexeme(source),
+        );
+
+        if trivia {
+            println!(
+                "{}",
+                line.if_supports_color(owo_colors::Stream::Stdout, |s| {
+                    s.style(owo_colors::Style::new().bright_black())
+                })
+            );
+        } else {
+            println!("{line}");
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors
+            .into_iter()
+            .map(|error| {
+                CompError::ScannerError(ScannerError {
+                    path,
+                    invalid_token: &source[error.span.range()],
+                    error,
+                    source,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Compiles the file at `path`, or the whole of stdin when `path` is `-`.
+fn compf<'src>(
+    path: &'src Path,
+    buf: &'src mut String,
+    emit: Emit,
+    sink: &mut DiagnosticSink<'src>,
+) -> Result<(), AppError<'src>> {
+    let (path, n) = if path == Path::new("-") {
+        let path = Path::new("<stdin>");
+        let n = stdin()
+            .read_to_string(buf)
+            .map_err(|e| AppError::FileRead(path, e))?;
+        (path, n)
+    } else {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .map_err(|e| AppError::FileRead(path, e))?;
+
+        let n = file
+            .read_to_string(buf)
+            .map_err(|e| AppError::FileRead(path, e))?;
+        (path, n)
+    };
+
+    if let Err(errors) = run(path, &buf[..n], emit, sink) {
+        for error in errors {
+            sink.push(error.diagnostic());
+        }
     }
 
     Ok(())
 }
 
-fn compf<'src>(path: &'src Path, buf: &'src mut String) -> Result<(), AppError<'src>> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .open(path)
-        .map_err(|e| AppError::FileRead(path, e))?;
+/// How compilation errors are shown.
+#[derive(Debug, Clone, Copy)]
+enum Report {
+    /// A rendered diagnostic per error, on stderr, with `context` lines
+    /// around the error.
+    Human { context: i16 },
+    /// A JSON array with every error, on stdout, see [`diag::to_json`].
+    #[cfg(feature = "serde")]
+    Json,
+}
 
-    let n = file
-        .read_to_string(buf)
-        .map_err(|e| AppError::FileRead(path, e))?;
+impl Report {
+    /// Where the errors are written to.
+    fn stream(self) -> Stream {
+        match self {
+            Report::Human { .. } => Stream::Stderr,
+            #[cfg(feature = "serde")]
+            Report::Json => Stream::Stdout,
+        }
+    }
 
-    run(path, &buf[..n]).map_err(|_| AppError::CompErrors)
+    fn render(self, sink: &mut DiagnosticSink<'_>) {
+        match self {
+            Report::Human { context } => {
+                let summary = sink.summary();
+                let diagnostics: Vec<_> = sink.drain().collect();
+                for diagnostic in diagnostics {
+                    eprintln!(
+                        "{}",
+                        sink.render(&diagnostic.with_context(-context..context))
+                    );
+                }
+                if let Some(summary) = summary {
+                    eprintln!("{summary}");
+                }
+            }
+            #[cfg(feature = "serde")]
+            Report::Json => {
+                let diagnostics: Vec<_> = sink.drain().collect();
+                println!("{}", diag::to_json(&diagnostics));
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -115,21 +429,44 @@ struct ScannerError<'src> {
 }
 
 #[derive(Debug)]
+struct RuntimeError<'src> {
+    path: &'src Path,
+    error: interpreter::Error,
+    source: &'src str,
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum CompError<'src> {
     ScannerError(ScannerError<'src>),
     ParserError(ParserError<'src>),
+    RuntimeError(RuntimeError<'src>),
 }
 
-impl std::fmt::Display for CompError<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'src> CompError<'src> {
+    fn span(&self) -> span::Span {
+        match self {
+            CompError::ScannerError(ScannerError { error, .. }) => error.span,
+            CompError::ParserError(ParserError { error, .. }) => error.span,
+            CompError::RuntimeError(RuntimeError { error, .. }) => error.span,
+        }
+    }
+
+    fn diagnostic(&self) -> Diagnostic<'src> {
         match self {
             CompError::ParserError(ParserError {
                 path,
                 source,
                 error,
-            }) => {
-                Diagnostic::new(source, path, error.span, format!("Parser error: {error:?}")).fmt(f)
-            }
+            }) => error.labels().into_iter().fold(
+                Diagnostic::new(
+                    source,
+                    path,
+                    error.span,
+                    format!("Parser error: {}", error.kind),
+                ),
+                |diag, (span, msg)| diag.with_label(span, msg),
+            ),
             CompError::ScannerError(ScannerError {
                 path: ruta,
                 invalid_token: token,
@@ -139,34 +476,129 @@ impl std::fmt::Display for CompError<'_> {
                 source,
                 ruta,
                 error.span,
-                format!("Scanner error with token {token:?}: {error:?}"),
-            )
-            .fmt(f),
+                format!("Scanner error with token {token:?}: {}", error.kind),
+            ),
+            CompError::RuntimeError(RuntimeError {
+                path,
+                error,
+                source,
+            }) => Diagnostic::new(
+                source,
+                path,
+                error.span,
+                format!("Runtime error: {}", error.kind),
+            ),
         }
     }
 }
 
+impl std::fmt::Display for CompError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.diagnostic().fmt(f)
+    }
+}
+
 #[derive(Debug)]
 enum AppError<'src> {
     FileRead(&'src Path, std::io::Error),
     WrongArgs,
-    CompErrors,
 }
 
+/// The stack of the thread `lox` runs on. Parsing recurses a few dozen
+/// frames per nesting level, so [`parser::MAX_DEPTH`] levels take more than
+/// the 8 MiB of the main thread in unoptimized builds.
+const STACK_SIZE: usize = 64 << 20;
+
 fn main() -> ExitCode {
-    let args: Vec<_> = args().skip(1).collect();
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("The thread can be spawned")
+        .join()
+        // The panic message is already printed
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn cli() -> ExitCode {
+    let mut args: Vec<_> = args().skip(1).collect();
+
+    let color = match args.iter().position(|arg| arg.starts_with("--color=")) {
+        Some(i) => {
+            let flag = args.remove(i);
+            match diag::ColorMode::from_flag(&flag["--color=".len()..]) {
+                Some(color) => color,
+                None => {
+                    eprintln!("Unknown {flag:?}, expected always, auto or never");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        None => diag::ColorMode::default(),
+    };
+    color.apply();
+
+    let context = match args.iter().position(|arg| arg == "--context") {
+        Some(i) => {
+            args.remove(i);
+            let lines = (i < args.len()).then(|| args.remove(i));
+            match lines.as_deref().map(str::parse::<u8>) {
+                Some(Ok(lines)) => i16::from(lines),
+                _ => {
+                    eprintln!("--context expects the number of lines to show");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        None => 1,
+    };
+
+    let report = match args.first().map(String::as_str) {
+        #[cfg(feature = "serde")]
+        Some("--emit=diagnostics-json") => Report::Json,
+        _ => Report::Human { context },
+    };
     let mut buf = String::new();
+    let mut sink = DiagnosticSink::new(report.stream());
 
     let res = match args.as_slice() {
         [] => {
             editline(&mut buf);
             Ok(())
         }
-        [file] => compf(Path::new(file), &mut buf),
+        [file] => compf(Path::new(file), &mut buf, Emit::Value, &mut sink),
+        [flag, file] if flag == "--tokens" => {
+            compf(Path::new(file), &mut buf, Emit::Tokens, &mut sink)
+        }
+        [flag, file] if flag == "--ast" => compf(Path::new(file), &mut buf, Emit::Ast, &mut sink),
+        [flag, expr] if flag == "-e" => {
+            let mut interpreter = interpreter::Interpreter::new();
+            let path = Path::new("<-e>");
+            if let Err(errors) =
+                run_expression(path, expr, Emit::Value, &mut interpreter, &mut sink)
+            {
+                for error in errors {
+                    sink.push(error.diagnostic());
+                }
+            }
+            Ok(())
+        }
+        #[cfg(feature = "serde")]
+        [emit, file] if emit == "--emit=ast-json" || emit == "--ast=json" => {
+            compf(Path::new(file), &mut buf, Emit::AstJson, &mut sink)
+        }
+        #[cfg(feature = "serde")]
+        [emit, file] if emit == "--emit=diagnostics-json" => {
+            compf(Path::new(file), &mut buf, Emit::Check, &mut sink)
+        }
         _ => Err(AppError::WrongArgs),
     };
 
+    let failed = sink.has_errors();
+    // Rendered even when empty, an empty JSON array tells editors the file is clean
+    report.render(&mut sink);
+
     match res {
+        Ok(_) if failed => ExitCode::FAILURE,
         Ok(_) => ExitCode::SUCCESS,
         Err(err) => {
             match err {
@@ -174,7 +606,6 @@ fn main() -> ExitCode {
                 AppError::FileRead(file, error) => {
                     eprintln!("Failed to read {:?}: {}", file.display(), error)
                 }
-                _ => {}
             }
             ExitCode::FAILURE
         }
@@ -0,0 +1,168 @@
+use crate::{
+    ast::{BinaryKind, Expression, ExpressionItem},
+    interpreter::{Interpreter, Value},
+};
+
+/// Evaluates the sub-trees of `expr` made only of literals, so `2 + 3 * 4`
+/// becomes the number `14` spanning the whole sum. Operations that would
+/// fail at runtime, and divisions by zero, are left for the runtime to
+/// report.
+pub fn fold_constants(expr: Expression) -> Expression {
+    let fold = |expr: Box<Expression>| Box::new(fold_constants(*expr));
+    let span = expr.span;
+
+    let item = match expr.item {
+        ExpressionItem::Binary(lhs, rhs, kind, operator) => {
+            ExpressionItem::Binary(fold(lhs), fold(rhs), kind, operator)
+        }
+        ExpressionItem::Logical(lhs, rhs, kind) => {
+            ExpressionItem::Logical(fold(lhs), fold(rhs), kind)
+        }
+        ExpressionItem::Unary(operand, kind) => ExpressionItem::Unary(fold(operand), kind),
+        ExpressionItem::Grouping(inner) => ExpressionItem::Grouping(fold(inner)),
+        ExpressionItem::Call {
+            callee,
+            paren_span,
+            args,
+        } => ExpressionItem::Call {
+            callee: fold(callee),
+            paren_span,
+            args: args.into_iter().map(fold_constants).collect(),
+        },
+        ExpressionItem::Assign { name, value } => ExpressionItem::Assign {
+            name,
+            value: fold(value),
+        },
+        ExpressionItem::Get { object, name } => ExpressionItem::Get {
+            object: fold(object),
+            name,
+        },
+        ExpressionItem::Set {
+            object,
+            name,
+            value,
+        } => ExpressionItem::Set {
+            object: fold(object),
+            name,
+            value: fold(value),
+        },
+        ExpressionItem::Comma(exprs) => {
+            ExpressionItem::Comma(exprs.into_iter().map(fold_constants).collect())
+        }
+        item => item,
+    };
+
+    let expr = Expression { span, item };
+    match evaluate_constant(&expr) {
+        Some(item) => Expression { span, item },
+        None => expr,
+    }
+}
+
+fn is_literal(expr: &Expression) -> bool {
+    matches!(
+        expr.item,
+        ExpressionItem::Number(_)
+            | ExpressionItem::String(_)
+            | ExpressionItem::Bool(_)
+            | ExpressionItem::Nil
+    )
+}
+
+/// The literal `expr` evaluates to, if its operands are already literals
+/// and evaluating it succeeds.
+fn evaluate_constant(expr: &Expression) -> Option<ExpressionItem> {
+    match &expr.item {
+        ExpressionItem::Binary(_, rhs, BinaryKind::Slash | BinaryKind::Mod, _)
+            if rhs.item == ExpressionItem::Number(0.0) =>
+        {
+            return None;
+        }
+        ExpressionItem::Binary(lhs, rhs, ..) | ExpressionItem::Logical(lhs, rhs, _)
+            if is_literal(lhs) && is_literal(rhs) => {}
+        ExpressionItem::Unary(operand, _) | ExpressionItem::Grouping(operand)
+            if is_literal(operand) => {}
+        _ => return None,
+    }
+
+    match Interpreter::new().evaluate(expr).ok()? {
+        Value::Number(n) => Some(ExpressionItem::Number(n)),
+        Value::String(s) => Some(ExpressionItem::String(s)),
+        Value::Bool(b) => Some(ExpressionItem::Bool(b)),
+        Value::Nil => Some(ExpressionItem::Nil),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fold_constants;
+    use crate::{
+        ast::{self, Expression, ExpressionItem},
+        parser::Parser,
+        scanner::Scanner,
+        span::Span,
+    };
+
+    fn fold(source: &str) -> Expression {
+        let (tokens, _) = Scanner::new(source).tokenize();
+        let expr = Parser::new(&tokens, source)
+            .parse()
+            .expect("It's guaranteed to parse");
+
+        fold_constants(expr)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            fold("2 + 3 * 4"),
+            Expression {
+                span: Span::from(0..9),
+                item: ExpressionItem::Number(14.0)
+            }
+        );
+        assert_eq!(ast::print(&fold("-(1 + 1) ** 2")), "-4");
+        assert_eq!(ast::print(&fold("!true")), "false");
+        assert_eq!(ast::print(&fold("1 < 2 and nil")), "nil");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            fold("\"a\" + \"b\""),
+            Expression {
+                span: Span::from(0..9),
+                item: ExpressionItem::String("ab".to_string())
+            }
+        );
+        assert_eq!(ast::print(&fold("\"ab\" * 2")), "\"abab\"");
+    }
+
+    #[test]
+    fn only_constant_sub_trees() {
+        let expr = fold("x + 2 * 3");
+        assert_eq!(ast::print(&expr), "(+ x 6)");
+        let ExpressionItem::Binary(_, rhs, ..) = &expr.item else {
+            panic!("The sum stays");
+        };
+        assert_eq!(rhs.span, Span::from(4..9));
+
+        assert_eq!(
+            ast::print(&fold("f(1 + 1, y = 2 - 3)")),
+            "(call f 2 (= y -1))"
+        );
+    }
+
+    #[test]
+    fn errors_are_not_folded() {
+        assert_eq!(ast::print(&fold("1 / 0")), "(/ 1 0)");
+        assert_eq!(ast::print(&fold("1 + nil")), "(+ 1 nil)");
+        assert_eq!(ast::print(&fold("-\"a\" + 1")), "(+ (- \"a\") 1)");
+        assert_eq!(ast::print(&fold("\"a\" * -1")), "(* \"a\" -1)");
+        assert!(matches!(
+            fold("\"a\" * 1e300").item,
+            ExpressionItem::Binary(..)
+        ));
+    }
+}
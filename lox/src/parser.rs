@@ -1,33 +1,252 @@
-use std::path::Path;
-
-use crate::{ast, diag::Diagnostic, scanner::Tk};
+use crate::{ast, scanner::Tk};
 pub use crate::{
     scanner::{Token, TokenKind},
     span::Span,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Parser<'src> {
-    ruta: &'src Path,
     source: &'src str,
-    tokens: &'src [Token],
+    /// The significant tokens, without whitespace or comments.
+    tokens: Vec<Token>,
     prev: Token,
     cursor: usize,
+    /// Errors recovered from while parsing, see [`Parser::parse_partial`].
+    errors: Vec<Error>,
+    warnings: Vec<Warning>,
+    /// How many recursive rules are currently being parsed.
+    depth: usize,
+    max_depth: usize,
+}
+
+/// Where the parser was, to go back there after parsing ahead, see
+/// [`Parser::checkpoint`].
+#[derive(Debug)]
+struct Checkpoint {
+    cursor: usize,
+    prev: Token,
+    errors: usize,
+    warnings: usize,
 }
 
+/// A token other than the ones the grammar allows, maybe `because` of an
+/// earlier token like the `(` a `)` closes. That token is kept whole, so a
+/// diagnostic can point at it too.
 #[derive(Debug)]
-struct UnexpectedTokenKind {
-    because: Option<TokenKind>,
-    expected: Vec<TokenKind>,
-    found: TokenKind,
+pub struct UnexpectedTokenKind {
+    pub because: Option<Token>,
+    pub expected: Vec<TokenKind>,
+    pub found: TokenKind,
 }
 
 #[derive(Debug)]
 pub enum ErrorKind {
     UnexpectedTokenKind(UnexpectedTokenKind),
     Eof,
+    InvalidAssignmentTarget,
+    UnclosedBlock,
+    TooManyArguments,
+    ExpectedPropertyName {
+        found: TokenKind,
+    },
+    /// A statement not ended by `;`, reported right after its last token
+    /// rather than at the `found` one, which may well be on the next line.
+    MissingSemicolon {
+        found: TokenKind,
+    },
+    BareSuper,
+    /// A number token that doesn't parse as one, which the scanner should
+    /// never produce.
+    InvalidNumber,
+    MissingLeftOperand {
+        operator: TokenKind,
+    },
+    TooDeep,
+}
+
+impl std::fmt::Display for UnexpectedTokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected ")?;
+        for (i, kind) in self.expected.iter().enumerate() {
+            match i {
+                0 => {}
+                i if i == self.expected.len() - 1 => write!(f, " or ")?,
+                _ => write!(f, ", ")?,
+            }
+            write!(f, "{kind}")?;
+        }
+        match self.because {
+            Some(open) if open.tipo == Tk::LeftParen && self.expected == [Tk::RightParen] => {
+                write!(f, " to close {}", open.tipo)?
+            }
+            Some(because) => write!(f, " after {}", because.tipo)?,
+            None => {}
+        }
+        write!(f, ", found {}", self.found)
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::UnexpectedTokenKind(err) => err.fmt(f),
+            ErrorKind::Eof => write!(f, "unexpected end of file"),
+            ErrorKind::InvalidAssignmentTarget => write!(f, "invalid assignment target"),
+            ErrorKind::UnclosedBlock => write!(f, "this `{{` is never closed"),
+            ErrorKind::TooManyArguments => {
+                write!(f, "can't have more than {MAX_ARGUMENTS} arguments")
+            }
+            ErrorKind::ExpectedPropertyName { .. } => {
+                write!(f, "expected property name after '.'")
+            }
+            ErrorKind::MissingSemicolon { .. } => write!(f, "expected `;` after expression"),
+            ErrorKind::MissingLeftOperand { operator } => {
+                write!(f, "{operator} needs a left-hand operand")
+            }
+            ErrorKind::BareSuper => write!(f, "'super' must be followed by '.' and a method name"),
+            ErrorKind::TooDeep => write!(f, "too deeply nested"),
+            ErrorKind::InvalidNumber => write!(f, "invalid number literal"),
+        }
+    }
+}
+
+/// Code that parses but is likely a mistake.
+#[derive(Debug)]
+pub struct Warning {
+    pub span: Span,
+    pub kind: WarningKind,
+}
+
+impl Warning {
+    /// Secondary spans worth pointing at along with [`Warning::span`], like
+    /// how to fix an assignment used as a condition.
+    pub fn labels(&self) -> Vec<(Span, String)> {
+        match self.kind {
+            WarningKind::AssignmentInCondition { condition } => vec![(
+                condition,
+                "help: use `==` to compare, or wrap in parentheses to silence".to_string(),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WarningKind {
+    /// `a < b < c`, which compares the bool `a < b` with `c`. Reported at
+    /// the second operator.
+    ChainedComparison,
+    /// `while (x = 1)`, likely meant as `x == 1`. Reported at the `=`, the
+    /// help goes under the whole `condition`.
+    AssignmentInCondition { condition: Span },
+    /// An integer too large for a number to hold, so it becomes `value`.
+    InexactNumber { value: f64 },
 }
 
+impl std::fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WarningKind::ChainedComparison => write!(
+                f,
+                "comparisons cannot be chained; did you mean `a < b and b < c`?"
+            ),
+            WarningKind::AssignmentInCondition { .. } => {
+                write!(f, "assignment used as a condition")
+            }
+            WarningKind::InexactNumber { value } => write!(
+                f,
+                "integer literal can't be represented exactly, it becomes {value:.0}"
+            ),
+        }
+    }
+}
+
+/// How tightly a binary operator holds its operands, higher binds tighter.
+/// The levels are those of [`ast::BinaryKind::precedence`].
+type BindingPower = u8;
+
+/// An operator between two operands, see [`infix`].
+#[derive(Debug)]
+enum Infix {
+    Binary(ast::BinaryKind),
+    Logical(ast::LogicalKind),
+}
+
+/// The operator `tipo` stands for between two operands, parsed by
+/// [`Parser::binary`], and how tightly it binds. `**` and `=` aren't in the
+/// table, they are right-associative and get rules of their own.
+fn infix(tipo: TokenKind) -> Option<(BindingPower, Infix)> {
+    let operator = match tipo {
+        Tk::Or => Infix::Logical(ast::LogicalKind::Or),
+        Tk::And => Infix::Logical(ast::LogicalKind::And),
+        Tk::EqualEqual => Infix::Binary(ast::BinaryKind::EqualEqual),
+        Tk::BangEqual => Infix::Binary(ast::BinaryKind::BangEqual),
+        Tk::Less => Infix::Binary(ast::BinaryKind::Less),
+        Tk::LessEqual => Infix::Binary(ast::BinaryKind::LessEqual),
+        Tk::Greater => Infix::Binary(ast::BinaryKind::Greater),
+        Tk::GreaterEqual => Infix::Binary(ast::BinaryKind::GreaterEqual),
+        Tk::Pipe => Infix::Binary(ast::BinaryKind::BitOr),
+        Tk::Caret => Infix::Binary(ast::BinaryKind::BitXor),
+        Tk::Ampersand => Infix::Binary(ast::BinaryKind::BitAnd),
+        Tk::LessLess => Infix::Binary(ast::BinaryKind::Shl),
+        Tk::GreaterGreater => Infix::Binary(ast::BinaryKind::Shr),
+        Tk::Plus => Infix::Binary(ast::BinaryKind::Plus),
+        Tk::Minus => Infix::Binary(ast::BinaryKind::Minus),
+        Tk::Star => Infix::Binary(ast::BinaryKind::Star),
+        Tk::Slash => Infix::Binary(ast::BinaryKind::Slash),
+        Tk::Percent => Infix::Binary(ast::BinaryKind::Mod),
+        _ => return None,
+    };
+
+    let power = match &operator {
+        Infix::Binary(kind) => kind.precedence(),
+        Infix::Logical(kind) => kind.precedence(),
+    };
+    Some((power, operator))
+}
+
+/// The operator a compound assignment like `x += 1` applies, desugared by
+/// [`Parser::assignment`] into `x = x + 1`.
+fn compound_assignment(tipo: TokenKind) -> Option<ast::BinaryKind> {
+    Some(match tipo {
+        Tk::PlusEqual => ast::BinaryKind::Plus,
+        Tk::MinusEqual => ast::BinaryKind::Minus,
+        Tk::StarEqual => ast::BinaryKind::Star,
+        Tk::SlashEqual => ast::BinaryKind::Slash,
+        Tk::PercentEqual => ast::BinaryKind::Mod,
+        _ => return None,
+    })
+}
+
+/// Whether `num` is exactly the integer written as `lexeme`. Literals with
+/// a fraction or an exponent are rounded anyway, they are never inexact.
+fn is_exact(lexeme: &str, num: f64) -> bool {
+    if !lexeme.bytes().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+    match lexeme.trim_start_matches('0') {
+        "" => true,
+        // The digits of the stored value, `to_string` would give the
+        // shortest ones parsing back to it
+        digits => format!("{num:.0}") == digits,
+    }
+}
+
+fn is_comparison(tipo: TokenKind) -> bool {
+    matches!(
+        tipo,
+        Tk::Less | Tk::LessEqual | Tk::Greater | Tk::GreaterEqual
+    )
+}
+
+/// The most arguments a call can pass.
+pub const MAX_ARGUMENTS: usize = 255;
+
+/// How deep expressions and statements may nest by default, see
+/// [`Parser::with_max_depth`]. Each level takes a few dozen stack frames,
+/// unoptimized builds need about 16 MiB of stack to reach it.
+pub const MAX_DEPTH: usize = 512;
+
 type Result<T> = std::prelude::rust_2021::Result<T, Error>;
 
 #[derive(Debug)]
@@ -36,13 +255,64 @@ pub struct Error {
     pub kind: ErrorKind,
 }
 
+impl Error {
+    /// Whether the parser ran out of tokens, meaning more input could still
+    /// complete the expression.
+    pub fn is_eof(&self) -> bool {
+        match &self.kind {
+            ErrorKind::Eof | ErrorKind::UnclosedBlock => true,
+            ErrorKind::UnexpectedTokenKind(err) => err.found == Tk::Eof,
+            ErrorKind::ExpectedPropertyName { found } | ErrorKind::MissingSemicolon { found } => {
+                *found == Tk::Eof
+            }
+            ErrorKind::InvalidAssignmentTarget
+            | ErrorKind::TooManyArguments
+            | ErrorKind::BareSuper
+            | ErrorKind::InvalidNumber
+            | ErrorKind::MissingLeftOperand { .. }
+            | ErrorKind::TooDeep => false,
+        }
+    }
+
+    /// Whether parsing can't go on past this error, so recovering from it
+    /// would only report it again.
+    pub fn aborts(&self) -> bool {
+        self.is_eof() || matches!(self.kind, ErrorKind::TooDeep)
+    }
+
+    /// Secondary spans worth pointing at along with [`Error::span`], like
+    /// the `(` a missing `)` would have closed.
+    pub fn labels(&self) -> Vec<(Span, String)> {
+        match &self.kind {
+            ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
+                because: Some(open),
+                expected,
+                ..
+            }) if open.tipo == Tk::LeftParen && expected == &[Tk::RightParen] => {
+                vec![(open.span, "opened here".to_string())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
 impl<'src> Parser<'src> {
-    pub fn new(ruta: &'src Path, tokens: &'src [Token], source: &'src str) -> Parser<'src> {
+    /// A parser over `tokens`, which may still hold the whitespace and
+    /// comments of the source: they are skipped here. So is the closing
+    /// [`TokenKind::Eof`], the parser stops when it runs out of tokens.
+    pub fn new(tokens: &[Token], source: &'src str) -> Parser<'src> {
         Parser {
-            ruta,
-            tokens,
+            tokens: tokens
+                .iter()
+                .filter(|token| !token.tipo.is_trivia() && token.tipo != Tk::Eof)
+                .copied()
+                .collect(),
             cursor: 0,
             source,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            depth: 0,
+            max_depth: MAX_DEPTH,
             prev: Token {
                 tipo: TokenKind::Eof,
                 span: Span::from(0..1),
@@ -50,28 +320,58 @@ impl<'src> Parser<'src> {
         }
     }
 
-    fn try_parse<T>(&self, mut f: impl FnMut(&mut Self) -> Result<T>) -> Option<(T, usize)> {
-        let mut p = *self;
-        let res = f(&mut p);
+    /// The warnings found so far, in source order.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
 
-        res.ok().map(|res| (res, p.cursor))
+    /// Limits nesting to `max_depth` levels instead of [`MAX_DEPTH`].
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
 
-    fn err_span(&self, span: Span, kind: ErrorKind) -> Error {
-        Error { span, kind }
+    /// Runs the recursive rule `rule` one level deeper, failing with
+    /// [`ErrorKind::TooDeep`] instead of overflowing the stack.
+    fn nested<T>(&mut self, rule: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_depth {
+            let span = self.peek().map_or(self.prev.span, |token| token.span);
+            return Err(self.err_span(span, ErrorKind::TooDeep));
+        }
+
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
     }
 
-    fn err(&self, kind: ErrorKind) -> Error {
-        self.err_span(self.span(), kind)
+    fn err_span(&self, span: Span, kind: ErrorKind) -> Error {
+        Error { span, kind }
     }
 
     fn primary(&mut self) -> Result<ast::Expression> {
-        if let Some(t @ Token { tipo, span }) = self.advance() {
+        // Left in place so the statement or block around recovers at them
+        let token = match self.peek() {
+            Some(Token {
+                tipo: Tk::RightBrace | Tk::Semicolon,
+                ..
+            }) => self.peek(),
+            _ => self.advance(),
+        };
+
+        if let Some(token @ Token { tipo, span }) = token {
             match tipo {
                 Tk::Number => {
-                    let num = self.source[span.range()]
+                    let lexeme = token.lexeme(self.source);
+                    let num = lexeme
                         .parse()
-                        .expect("The lexer does return a valid number span");
+                        .map_err(|_| self.err_span(span, ErrorKind::InvalidNumber))?;
+                    if !is_exact(lexeme, num) {
+                        self.warnings.push(Warning {
+                            span,
+                            kind: WarningKind::InexactNumber { value: num },
+                        });
+                    }
                     return Ok(ast::Expression {
                         span,
                         item: ast::ExpressionItem::Number(num),
@@ -93,7 +393,7 @@ impl<'src> Parser<'src> {
                     return Ok(ast::Expression {
                         span,
                         item: ast::ExpressionItem::String(
-                            self.source[span.range()].trim_matches('"').to_string(),
+                            token.string_value(self.source).into_owned(),
                         ),
                     });
                 }
@@ -103,28 +403,74 @@ impl<'src> Parser<'src> {
                         item: ast::ExpressionItem::Nil,
                     });
                 }
+                Tk::Identifier => {
+                    return Ok(ast::Expression {
+                        span,
+                        item: ast::ExpressionItem::Variable(token.lexeme(self.source).to_string()),
+                    });
+                }
+                Tk::This => {
+                    return Ok(ast::Expression {
+                        span,
+                        item: ast::ExpressionItem::This,
+                    });
+                }
+                Tk::Super => {
+                    let Some(Token { tipo: Tk::Dot, .. }) = self.peek() else {
+                        return Err(Error {
+                            span,
+                            kind: ErrorKind::BareSuper,
+                        });
+                    };
+                    self.bump();
+                    let method = self.identifier(self.prev)?;
+
+                    return Ok(ast::Expression {
+                        span: span.join(method.span),
+                        item: ast::ExpressionItem::Super {
+                            keyword_span: span,
+                            method,
+                        },
+                    });
+                }
+                Tk::Fun => {
+                    let (params, body) = self.nested(Self::parameters_and_body)?;
+
+                    return Ok(ast::Expression {
+                        span: span.join(self.prev.span),
+                        item: ast::ExpressionItem::Lambda { params, body },
+                    });
+                }
                 TokenKind::LeftParen => {
-                    let expr = self.comparison()?;
-
-                    let token = self.peek().unwrap_or(t);
-                    if token.tipo != Tk::RightParen {
-                        Diagnostic::new(
-                            self.source,
-                            self.ruta,
-                            token.span,
-                            "Unclosed (".to_string(),
-                        )
-                        .err();
-                    }
+                    let open = self.prev;
+                    let expr = self.expression()?;
+                    let close = self.expect(Tk::RightParen, Some(open))?;
 
-                    return Ok(expr);
+                    return Ok(ast::Expression {
+                        span: span.join(close.span),
+                        item: ast::ExpressionItem::Grouping(Box::new(expr)),
+                    });
                 }
                 x => {
+                    if self.discard_right_operand(x) {
+                        return Err(Error {
+                            span,
+                            kind: ErrorKind::MissingLeftOperand { operator: x },
+                        });
+                    }
+
                     return Err(Error {
                         span,
                         kind: ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
                             because: None,
-                            expected: vec![Tk::Number, Tk::True, Tk::False, Tk::String, Tk::Nil],
+                            expected: vec![
+                                Tk::Number,
+                                Tk::True,
+                                Tk::False,
+                                Tk::String,
+                                Tk::Nil,
+                                Tk::Identifier,
+                            ],
                             found: x,
                         }),
                     });
@@ -142,6 +488,7 @@ impl<'src> Parser<'src> {
                     Tk::False,
                     Tk::String,
                     Tk::Nil,
+                    Tk::Identifier,
                     Tk::LeftParen,
                 ],
                 found: TokenKind::Eof,
@@ -149,17 +496,29 @@ impl<'src> Parser<'src> {
         })
     }
 
-    fn unary(&mut self) -> Result<ast::Expression> {
-        'l: loop {
-            match self.partial_next_chunk::<2>().map(|t| t.tipo) {
-                [Tk::Bang, Tk::Bang] | [Tk::Minus, Tk::Minus] => {
-                    self.bump_n(2);
-                }
-                _ => break 'l,
-            }
+    /// Error production for a binary `operator` found where an expression
+    /// should start, like `* 3`. Parses the right operand that would follow
+    /// it and throws it away, so the errors after it are still accurate.
+    /// Returns whether `operator` is a binary operator at all.
+    fn discard_right_operand(&mut self, operator: TokenKind) -> bool {
+        // A leading `-` is a negation, never a subtraction
+        if !operator.is_binary_operator() || operator == Tk::Minus {
+            return false;
         }
 
-        if let Some(Token { tipo, .. }) = self.peek()
+        let _ = match infix(operator) {
+            Some((power, _)) => self.binary(power + 1),
+            None => self.unary(),
+        };
+        true
+    }
+
+    fn unary(&mut self) -> Result<ast::Expression> {
+        self.nested(Self::unary_inner)
+    }
+
+    fn unary_inner(&mut self) -> Result<ast::Expression> {
+        if let Some(Token { tipo, span }) = self.peek()
             && (tipo == Tk::Minus || tipo == Tk::Bang)
         {
             let kind = match tipo {
@@ -169,357 +528,2747 @@ impl<'src> Parser<'src> {
             };
 
             self.bump();
-            let unary = match self.unary() {
-                Ok(unary) => unary,
-                Err(err) => {
-                    Diagnostic::new(
-                        self.source,
-                        self.ruta,
-                        err.span,
-                        format!("Expected unary, but found error {err:?}"),
-                    )
-                    .err();
-                    return self.primary();
-                }
-            };
+            let unary = self.unary()?;
             return Ok(ast::Expression {
-                span: unary.span,
+                span: span.join(unary.span),
                 item: ast::ExpressionItem::Unary(Box::new(unary), kind),
             });
         };
 
-        self.primary()
+        self.power()
     }
 
-    fn factor(&mut self) -> Result<ast::Expression> {
-        let mut lhs = self.unary()?;
+    /// `**` is right-associative and binds tighter than a unary operator on
+    /// its left, so `-2 ** 2` is `-(2 ** 2)`, while its right operand may
+    /// still be negated: `2 ** -1`.
+    fn power(&mut self) -> Result<ast::Expression> {
+        let lhs = self.call()?;
 
-        while let Some(Token { tipo, .. }) = self.peek()
-            && (tipo == Tk::Star || tipo == Tk::Slash)
+        if let Some(
+            operator @ Token {
+                tipo: Tk::StarStar, ..
+            },
+        ) = self.peek()
         {
-            let kind = match tipo {
-                Tk::Star => ast::BinaryKind::Star,
-                Tk::Slash => ast::BinaryKind::Slash,
-                _ => unreachable!("We did check it before"),
-            };
-
             self.bump();
             let rhs = match self.unary() {
                 Ok(rhs) => rhs,
+                Err(err) if err.aborts() => return Err(err),
                 Err(err) => {
-                    Diagnostic::new(
-                        self.source,
-                        self.ruta,
-                        err.span,
-                        format!("Expected unary, but found error {err:?}"),
-                    )
-                    .err();
-                    break;
+                    self.errors.push(err);
+                    return Ok(lhs);
                 }
             };
 
-            lhs = ast::Expression {
+            return Ok(ast::Expression {
                 span: lhs.span.join(rhs.span),
-                item: ast::ExpressionItem::Binary(Box::new(lhs), Box::new(rhs), kind),
-            };
+                item: ast::ExpressionItem::Binary(
+                    Box::new(lhs),
+                    Box::new(rhs),
+                    ast::BinaryKind::Pow,
+                    operator.span,
+                ),
+            });
         }
 
         Ok(lhs)
     }
 
-    fn term(&mut self) -> Result<ast::Expression> {
-        let mut lhs = self.factor()?;
-
-        while let Some(Token { tipo, .. }) = self.peek()
-            && (tipo == Tk::Plus || tipo == Tk::Minus)
-        {
-            let kind = match tipo {
-                Tk::Minus => ast::BinaryKind::Minus,
-                Tk::Plus => ast::BinaryKind::Plus,
-                _ => unreachable!("We did check it before"),
-            };
-
-            self.bump();
-            let rhs = match self.factor() {
-                Ok(rhs) => rhs,
-                Err(err) => {
-                    Diagnostic::new(
-                        self.source,
-                        self.ruta,
-                        err.span,
-                        format!("Expected factor, but found error {err:?}"),
-                    )
-                    .err();
-                    break;
+    /// A primary followed by any number of argument lists, `f(1)(2)`.
+    fn call(&mut self) -> Result<ast::Expression> {
+        let mut callee = self.primary()?;
+
+        loop {
+            match self.peek() {
+                Some(
+                    open @ Token {
+                        tipo: Tk::LeftParen,
+                        ..
+                    },
+                ) => {
+                    self.bump();
+                    let args = self.arguments()?;
+                    let paren = self.expect(Tk::RightParen, Some(open))?;
+
+                    callee = ast::Expression {
+                        span: callee.span.join(paren.span),
+                        item: ast::ExpressionItem::Call {
+                            callee: Box::new(callee),
+                            paren_span: paren.span,
+                            args,
+                        },
+                    };
                 }
-            };
-
-            lhs = ast::Expression {
-                span: lhs.span.join(rhs.span),
-                item: ast::ExpressionItem::Binary(Box::new(lhs), Box::new(rhs), kind),
-            };
+                Some(Token {
+                    tipo: Tk::Dot,
+                    span: dot,
+                }) => {
+                    self.bump();
+                    let span = match self.peek() {
+                        Some(Token {
+                            tipo: Tk::Identifier,
+                            span,
+                        }) => {
+                            self.bump();
+                            span
+                        }
+                        token => {
+                            return Err(Error {
+                                span: token.map_or(Span::from(dot.end..dot.end), |t| t.span),
+                                kind: ErrorKind::ExpectedPropertyName {
+                                    found: token.map_or(Tk::Eof, |t| t.tipo),
+                                },
+                            })
+                        }
+                    };
+
+                    callee = ast::Expression {
+                        span: callee.span.join(span),
+                        item: ast::ExpressionItem::Get {
+                            object: Box::new(callee),
+                            name: ast::Identifier {
+                                span,
+                                name: self.source[span.range()].to_string(),
+                            },
+                        },
+                    };
+                }
+                _ => return Ok(callee),
+            }
         }
-
-        Ok(lhs)
     }
 
-    fn comparison(&mut self) -> Result<ast::Expression> {
-        let mut lhs = self.term()?;
+    /// The comma separated arguments of a call. Passing more than
+    /// [`MAX_ARGUMENTS`] is recorded as an error but parsing goes on.
+    fn arguments(&mut self) -> Result<Vec<ast::Expression>> {
+        let mut args = Vec::new();
 
-        while let Some(Token { tipo, .. }) = self.peek()
-            && (tipo == Tk::Less
-                || tipo == Tk::LessEqual
-                || tipo == Tk::GreaterEqual
-                || tipo == Tk::Greater)
+        if let Some(Token {
+            tipo: Tk::RightParen,
+            ..
+        }) = self.peek()
         {
-            let kind = match tipo {
-                Tk::Less => ast::BinaryKind::Less,
-                Tk::LessEqual => ast::BinaryKind::LessEqual,
-                Tk::GreaterEqual => ast::BinaryKind::GreaterEqual,
-                Tk::Greater => ast::BinaryKind::Greater,
-                _ => unreachable!("We did check it before"),
-            };
+            return Ok(args);
+        }
 
-            self.bump();
-            let rhs = match self.term() {
-                Ok(rhs) => rhs,
-                Err(err) => {
-                    Diagnostic::new(
-                        self.source,
-                        self.ruta,
-                        err.span,
-                        format!("Expected term, but found error {err:?}"),
-                    )
-                    .err();
-                    break;
-                }
-            };
+        loop {
+            let arg = self.assignment()?;
+            if args.len() == MAX_ARGUMENTS {
+                self.errors.push(Error {
+                    span: arg.span,
+                    kind: ErrorKind::TooManyArguments,
+                });
+            }
+            args.push(arg);
 
-            lhs = ast::Expression {
-                span: lhs.span.join(rhs.span),
-                item: ast::ExpressionItem::Binary(Box::new(lhs), Box::new(rhs), kind),
-            };
+            match self.peek() {
+                Some(Token {
+                    tipo: Tk::Comma, ..
+                }) => self.bump(),
+                _ => return Ok(args),
+            }
+            // A trailing comma
+            if self.peek().is_some_and(|t| t.tipo == Tk::RightParen) {
+                return Ok(args);
+            }
         }
-
-        Ok(lhs)
     }
 
-    fn equality(&mut self) -> Result<ast::Expression> {
-        let mut lhs = self.comparison()?;
+    /// Parses operands and the binary operators between them binding at
+    /// least as tightly as `min`. They are all left-associative, so the
+    /// right operand of each only takes the operators binding tighter.
+    fn binary(&mut self, min: BindingPower) -> Result<ast::Expression> {
+        let mut lhs = self.unary()?;
+        let mut prev_operator: Option<TokenKind> = None;
 
-        while let Some(Token { tipo, .. }) = self.peek()
-            && (tipo == Tk::EqualEqual || tipo == Tk::BangEqual)
+        while let Some(token @ Token { tipo, .. }) = self.peek()
+            && let Some((power, operator)) = infix(tipo)
+            && power >= min
         {
-            let kind = match tipo {
-                Tk::BangEqual => ast::BinaryKind::BangEqual,
-                Tk::EqualEqual => ast::BinaryKind::EqualEqual,
-                _ => unreachable!("We did check it before"),
-            };
-
             self.bump();
-            let rhs = match self.comparison() {
+            if let Some(prev) = prev_operator
+                && is_comparison(prev)
+                && is_comparison(tipo)
+            {
+                self.warnings.push(Warning {
+                    span: token.span,
+                    kind: WarningKind::ChainedComparison,
+                });
+            }
+            prev_operator = Some(tipo);
+
+            let rhs = match self.binary(power + 1) {
                 Ok(rhs) => rhs,
+                Err(err) if err.aborts() => return Err(err),
                 Err(err) => {
-                    Diagnostic::new(
-                        self.source,
-                        self.ruta,
-                        err.span,
-                        format!("Expected comparison, but found error {err:?}"),
-                    )
-                    .err();
+                    self.errors.push(err);
                     break;
                 }
             };
 
+            let span = lhs.span.join(rhs.span);
+            let (lhs_, rhs) = (Box::new(lhs), Box::new(rhs));
             lhs = ast::Expression {
-                span: lhs.span.join(rhs.span),
-                item: ast::ExpressionItem::Binary(Box::new(lhs), Box::new(rhs), kind),
+                span,
+                item: match operator {
+                    Infix::Binary(kind) => ast::ExpressionItem::Binary(lhs_, rhs, kind, token.span),
+                    Infix::Logical(kind) => ast::ExpressionItem::Logical(lhs_, rhs, kind),
+                },
             };
         }
 
         Ok(lhs)
     }
 
-    pub fn parse(&mut self) -> Result<ast::Expression> {
-        self.equality()
-        // if let Some((res, c)) = self.try_parse(Self::parse_annotated_number) {
-        //     self.bump_to(c);
-        //     Ok(res)
-        // } else {
-        //     Err(Error::Eof)
-        // }
+    fn expression(&mut self) -> Result<ast::Expression> {
+        self.comma()
     }
-}
 
-impl Parser<'_> {
-    fn bump_n(&mut self, n: usize) {
-        for _ in 0..n {
+    /// `a, b, c` evaluates every expression left to right and results in the
+    /// last one. Call arguments and `for` clauses start at
+    /// [`assignment`](Self::assignment) instead, so their commas aren't
+    /// taken as operators.
+    fn comma(&mut self) -> Result<ast::Expression> {
+        let first = self.assignment()?;
+        let mut exprs = vec![first];
+
+        while let Some(Token {
+            tipo: Tk::Comma, ..
+        }) = self.peek()
+        {
             self.bump();
+            exprs.push(self.assignment()?);
         }
-    }
 
-    fn bump_to(&mut self, cursor: usize) {
-        self.cursor = cursor;
-    }
+        if exprs.len() == 1 {
+            return Ok(exprs.pop().expect("There is one expression"));
+        }
 
-    fn bump(&mut self) {
-        self.prev = self.tokens[self.cursor];
-        self.cursor += 1;
+        Ok(ast::Expression {
+            span: exprs[0].span.join(exprs[exprs.len() - 1].span),
+            item: ast::ExpressionItem::Comma(exprs),
+        })
     }
 
-    fn track_bump(&mut self, track: &mut Span) {
-        if let Some(t) = self.peek() {
-            track.end = t.span.len();
+    /// `=` is right-associative, `a = b = 3` assigns 3 to `b` and then to
+    /// `a`. The target is parsed as an expression and only then checked to
+    /// be something that can be assigned to. Parsing it speculatively and
+    /// rewinding would parse every nested operand twice per level.
+    fn assignment(&mut self) -> Result<ast::Expression> {
+        let target = self.binary(0)?;
+
+        let Some(Token {
+            tipo,
+            span: operator_span,
+        }) = self.peek()
+        else {
+            return Ok(target);
+        };
+        let operator = compound_assignment(tipo);
+        if tipo != Tk::Equal && operator.is_none() {
+            return Ok(target);
         }
+
         self.bump();
-    }
+        let value = self.nested(Self::assignment)?;
+
+        // Only variables, `a.b += 1` would evaluate `a` twice
+        if let Some(kind) = operator {
+            let ast::ExpressionItem::Variable(name) = target.item else {
+                return Err(Error {
+                    span: target.span,
+                    kind: ErrorKind::InvalidAssignmentTarget,
+                });
+            };
+            let span = target.span.join(value.span);
+            let current = ast::Expression {
+                span: target.span,
+                item: ast::ExpressionItem::Variable(name.clone()),
+            };
+            return Ok(ast::Expression {
+                span,
+                item: ast::ExpressionItem::Assign {
+                    name: ast::Identifier {
+                        span: target.span,
+                        name,
+                    },
+                    value: Box::new(ast::Expression {
+                        span,
+                        item: ast::ExpressionItem::Binary(
+                            Box::new(current),
+                            Box::new(value),
+                            kind,
+                            operator_span,
+                        ),
+                    }),
+                },
+            });
+        }
 
-    fn prev_span(&self) -> Option<Span> {
-        self.tokens.get(self.cursor - 1).map(|s| s.span)
+        match target.item {
+            ast::ExpressionItem::Variable(name) => Ok(ast::Expression {
+                span: target.span.join(value.span),
+                item: ast::ExpressionItem::Assign {
+                    name: ast::Identifier {
+                        span: target.span,
+                        name,
+                    },
+                    value: Box::new(value),
+                },
+            }),
+            ast::ExpressionItem::Get { object, name } => Ok(ast::Expression {
+                span: target.span.join(value.span),
+                item: ast::ExpressionItem::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                },
+            }),
+            _ => Err(Error {
+                span: target.span,
+                kind: ErrorKind::InvalidAssignmentTarget,
+            }),
+        }
     }
 
-    fn span(&self) -> Span {
-        self.prev.span
+    /// Parses statements until the tokens run out, failing with every error
+    /// found in source order. See [`Parser::parse_partial`].
+    pub fn parse_program(&mut self) -> std::result::Result<ast::Program, Vec<Error>> {
+        match self.parse_partial() {
+            (program, errors) if errors.is_empty() => Ok(program),
+            (_, errors) => Err(errors),
+        }
     }
 
-    fn advance_n<const N: usize>(&mut self) -> Option<[Token; N]> {
-        let tokens = *self.next_chunk::<N>()?;
-        self.bump_n(N);
-        Some(tokens)
+    /// Parses statements until the tokens run out. A statement that fails
+    /// to parse is recorded and skipped up to the next statement, so every
+    /// bad statement is reported. Returns the statements that did parse
+    /// along with the errors, in source order.
+    pub fn parse_partial(&mut self) -> (ast::Program, Vec<Error>) {
+        let mut statements = Vec::new();
+
+        while self.peek().is_some() {
+            let start = self.cursor;
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                // What follows is likely as deeply nested, reporting it
+                // again wouldn't help
+                Err(
+                    err @ Error {
+                        kind: ErrorKind::TooDeep,
+                        ..
+                    },
+                ) => {
+                    self.errors.push(err);
+                    break;
+                }
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize();
+                    // Don't get stuck on a statement that fails before
+                    // consuming anything
+                    if self.cursor == start {
+                        self.bump();
+                    }
+                }
+            }
+        }
+
+        // An unclosed block is only found after the errors within it
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|err| err.span.start);
+
+        (ast::Program { statements }, errors)
     }
 
-    fn advance(&mut self) -> Option<Token> {
-        let [token] = self.advance_n::<1>()?;
-        Some(token)
+    /// Parses statements until the tokens run out, stopping at the first
+    /// error instead of recovering from it.
+    pub fn parse_strict(&mut self) -> Result<ast::Program> {
+        let mut statements = Vec::new();
+
+        while self.peek().is_some() {
+            let statement = self.declaration();
+            // Blocks recover from the errors within them, those come first
+            if !self.errors.is_empty() {
+                return Err(self.errors.remove(0));
+            }
+            statements.push(statement?);
+        }
+
+        Ok(ast::Program { statements })
     }
 
-    fn advance_track(&mut self, track: &mut Span) -> Option<Token> {
-        let advance = self.advance();
-        if let Some(ref t) = advance {
-            track.end = t.span.end;
+    fn declaration(&mut self) -> Result<ast::Statement> {
+        if self.consume_if(Tk::Var).is_some() {
+            return self.var_declaration();
+        }
+
+        // Without a name it is a lambda, parsed as an expression
+        if let Some(
+            &[keyword @ Token { tipo: Tk::Fun, .. }, Token {
+                tipo: Tk::Identifier,
+                ..
+            }],
+        ) = self.next_chunk::<2>()
+        {
+            self.bump();
+            return self.function(keyword).map(ast::Statement::Function);
+        }
+
+        if self.consume_if(Tk::Class).is_some() {
+            return self.class_declaration();
         }
-        advance
+
+        self.statement()
     }
 
-    ///
-    /// ```
-    /// let next3: Option<&[Token; 3]> = parser.next_chunk::<3>();
-    /// ```
-    fn next_chunk<const N: usize>(&self) -> Option<&[Token; N]> {
-        self.tokens[self.cursor..].first_chunk::<N>()
+    /// Parses what follows `var`: `name;` or `name = initializer;`.
+    fn var_declaration(&mut self) -> Result<ast::Statement> {
+        let name = self.identifier(self.prev)?;
+
+        let initializer = match self.consume_if(Tk::Equal) {
+            Some(equal) => Some(self.expression_after(equal)?),
+            None => None,
+        };
+        self.semicolon()?;
+
+        Ok(ast::Statement::Var { name, initializer })
     }
 
-    fn partial_next_chunk<const N: usize>(&self) -> [Token; N] {
-        let mut chunk = [Token::default(); N];
+    /// Parses what follows `class`: `Name < Superclass { methods }`, the
+    /// superclass being optional. A stray token where a method should start
+    /// is recorded and skipped, so the remaining methods are still checked.
+    fn class_declaration(&mut self) -> Result<ast::Statement> {
+        let keyword = self.prev;
+        let name = self.identifier(keyword)?;
 
-        let _ = (0..N).try_for_each(|i| {
-            if let Some(t) = self.tokens.get(self.cursor + i).copied() {
-                chunk[i] = t;
-                Ok(())
-            } else {
-                Err(())
+        let superclass = match self.consume_if(Tk::Less) {
+            Some(less) => Some(self.identifier(less)?),
+            None => None,
+        };
+
+        let open = self.expect(Tk::LeftBrace, Some(self.prev))?.span;
+        let mut methods = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Error {
+                        span: open,
+                        kind: ErrorKind::UnclosedBlock,
+                    })
+                }
+                Some(Token {
+                    tipo: Tk::RightBrace,
+                    ..
+                }) => {
+                    self.bump();
+                    break;
+                }
+                Some(Token {
+                    tipo: Tk::Identifier,
+                    ..
+                }) => methods.push(self.function(keyword)?),
+                Some(Token { tipo, span }) => {
+                    self.errors.push(Error {
+                        span,
+                        kind: ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
+                            because: Some(keyword),
+                            expected: vec![Tk::Identifier, Tk::RightBrace],
+                            found: tipo,
+                        }),
+                    });
+
+                    while self
+                        .peek()
+                        .is_some_and(|t| !matches!(t.tipo, Tk::Identifier | Tk::RightBrace))
+                    {
+                        self.bump();
+                    }
+                }
             }
-        });
+        }
 
-        chunk
+        Ok(ast::Statement::Class {
+            name,
+            superclass,
+            methods,
+        })
     }
 
-    fn lookup_n(&self, n: usize) -> Option<Token> {
-        self.tokens.get(self.cursor + n - 1).copied()
+    /// Parses `name(params) { body }`, the shape shared by functions and
+    /// the methods of a class, which `because` introduces.
+    fn function(&mut self, because: Token) -> Result<ast::Function> {
+        self.nested(|parser| parser.function_inner(because))
     }
 
-    fn peek(&self) -> Option<Token> {
-        self.lookup_n(1)
+    fn function_inner(&mut self, because: Token) -> Result<ast::Function> {
+        let name = self.identifier(because)?;
+        let (params, body) = self.parameters_and_body()?;
+
+        Ok(ast::Function { name, params, body })
     }
-}
 
-// #[cfg(test)]
-// mod test {
-//     use crate::{ast::Expression, scanner, span::Span};
-
-//     use super::Parser;
-
-//     #[test]
-//     fn parse_expr_number() {
-//         let source = "4";
-//         let lexer = scanner::Scanner::new(source);
-//         let tokens: Vec<_> = lexer
-//             .into_iter()
-//             .map(|a| a.expect("It's guaranteed to be valid"))
-//             .collect();
-
-//         let mut parser = Parser::new(&tokens, source);
-//         let res = parser.parse();
-
-//         println!("{:?}", res);
-
-//         assert_eq!(
-//             res,
-//             Some(Expression {
-//                 span: Span::from(0..1),
-//                 item: crate::ast::ExpressionItem::Literal(crate::ast::Literal {
-//                     span: Span::from(0..1),
-//                     item: crate::ast::LiteralItem::Number(4.0)
-//                 })
-//             })
-//         )
-//     }
-
-//     #[test]
-//     fn parse_expr_parent() {
-//         let source = "(4)";
-//         let lexer = scanner::Scanner::new(source);
-//         let tokens: Vec<_> = lexer
-//             .into_iter()
-//             .map(|a| a.expect("It's guaranteed to be valid"))
-//             .collect();
-
-//         let mut parser = Parser::new(tokens, source);
-//         let res = parser.parse_expression();
-
-//         println!("{:?}", res);
-
-//         assert_eq!(
-//             res,
-//             Some(Expression {
-//                 span: Span::from(0..0),
-//                 item: crate::ast::ExpressionItem::Grouping(Box::new(Expression {
-//                     span: Span::from(1..2),
-//                     item: crate::ast::ExpressionItem::Literal(crate::ast::Literal {
-//                         span: Span::from(1..2),
-//                         item: crate::ast::LiteralItem::Number(4.0)
-//                     })
-//                 }))
-//             })
-//         )
-//     }
-
-//     #[test]
-//     fn parse_expr_binary() {
-//         let source = "(4) + (5)";
-//         let lexer = scanner::Scanner::new(source);
-//         let tokens: Vec<_> = lexer
-//             .into_iter()
-//             .map(|a| a.expect("It's guaranteed to be valid"))
-//             .collect();
-
-//         let mut parser = Parser::new(tokens, source);
-//         let res = parser.parse_expression();
-
-//         println!("{:?}", res);
-
-//         // assert_eq!(
-//         //     res,
-//         //     Some(Expression {
-//         //         span: Span::from(0..0),
-//         //         item: crate::ast::ExpressionItem::Grouping(Box::new(Expression {
-//         //             span: Span::from(1..2),
-//         //             item: crate::ast::ExpressionItem::Literal(crate::ast::Literal {
-//         //                 span: Span::from(1..2),
-//         //                 item: crate::ast::LiteralItem::Number(4.0)
-//         //             })
-//         //         }))
-//         //     })
-//         // )
-//     }
-// }
+    /// Parses `(params) { body }`, what follows the name of a function or
+    /// the `fun` of a lambda.
+    fn parameters_and_body(&mut self) -> Result<(Vec<ast::Identifier>, Vec<ast::Statement>)> {
+        let open = self.expect(Tk::LeftParen, Some(self.prev))?;
+        let mut params = Vec::new();
+        if !self.peek().is_some_and(|t| t.tipo == Tk::RightParen) {
+            loop {
+                params.push(self.identifier(open)?);
+                match self.peek() {
+                    Some(Token {
+                        tipo: Tk::Comma, ..
+                    }) => {
+                        self.bump();
+                        // A trailing comma
+                        if self.peek().is_some_and(|t| t.tipo == Tk::RightParen) {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+        self.expect(Tk::RightParen, Some(open))?;
+
+        let open = self.expect(Tk::LeftBrace, Some(self.prev))?.span;
+        let body = self.block_statements(open)?;
+
+        Ok((params, body))
+    }
+
+    /// Warns when `condition` is an assignment, not wrapped in parentheses
+    /// of its own.
+    fn warn_assignment_condition(&mut self, condition: &ast::Expression) {
+        let target_end = match &condition.item {
+            ast::ExpressionItem::Assign { name, .. } | ast::ExpressionItem::Set { name, .. } => {
+                name.span.end
+            }
+            _ => return,
+        };
+
+        // The assignment operator comes right after the target's name, a
+        // compound one like `+=` can't be a mistyped `==`. The tokens are in
+        // source order, so a binary search finds it
+        let next = self.tokens.partition_point(|t| t.span.start < target_end);
+        if let Some(equal) = self.tokens.get(next).filter(|t| t.tipo == Tk::Equal) {
+            self.warnings.push(Warning {
+                span: equal.span,
+                kind: WarningKind::AssignmentInCondition {
+                    condition: condition.span,
+                },
+            });
+        }
+    }
+
+    /// Consumes the identifier that `because` requires.
+    fn identifier(&mut self, because: Token) -> Result<ast::Identifier> {
+        let name = self.expect(Tk::Identifier, Some(because))?;
+
+        Ok(ast::Identifier {
+            span: name.span,
+            name: name.lexeme(self.source).to_string(),
+        })
+    }
+
+    fn statement(&mut self) -> Result<ast::Statement> {
+        self.nested(Self::statement_inner)
+    }
+
+    fn statement_inner(&mut self) -> Result<ast::Statement> {
+        if let Some(open) = self.consume_if(Tk::LeftBrace) {
+            return self.block(open.span);
+        }
+
+        if let Some(keyword) = self.consume_if(Tk::Print) {
+            // The commas separate values to print rather than being operators,
+            // unless they are in parentheses
+            let values = match self.expression_after(keyword)? {
+                ast::Expression {
+                    item: ast::ExpressionItem::Comma(values),
+                    ..
+                } => values,
+                value => vec![value],
+            };
+            self.semicolon()?;
+
+            return Ok(ast::Statement::Print(values));
+        }
+
+        if let Some(Token {
+            tipo: tipo @ (Tk::Break | Tk::Continue),
+            span,
+        }) = self.peek()
+        {
+            self.bump();
+            self.semicolon()?;
+
+            return Ok(match tipo {
+                Tk::Break => ast::Statement::Break(span),
+                _ => ast::Statement::Continue(span),
+            });
+        }
+
+        if let Some(Token { span, .. }) = self.consume_if(Tk::Return) {
+            let value = match self.peek() {
+                None
+                | Some(Token {
+                    tipo: Tk::Semicolon,
+                    ..
+                }) => None,
+                Some(_) => Some(self.expression()?),
+            };
+            self.semicolon()?;
+
+            return Ok(ast::Statement::Return {
+                keyword_span: span,
+                value,
+            });
+        }
+
+        if self.consume_if(Tk::While).is_some() {
+            return self.while_statement();
+        }
+
+        if self.consume_if(Tk::For).is_some() {
+            return self.for_statement();
+        }
+
+        let expr = self.expression()?;
+        self.semicolon()?;
+
+        Ok(ast::Statement::Expression(expr))
+    }
+
+    /// Parses what follows `while`: `(condition) body`.
+    fn while_statement(&mut self) -> Result<ast::Statement> {
+        let open = self.expect(Tk::LeftParen, Some(self.prev))?;
+        let condition = self.expression_after(open)?;
+        self.warn_assignment_condition(&condition);
+        self.expect(Tk::RightParen, Some(open))?;
+        let body = self.statement()?;
+
+        Ok(ast::Statement::While {
+            condition,
+            body: Box::new(body),
+            increment: None,
+        })
+    }
+
+    /// Parses what follows `for`: `(initializer; condition; increment) body`,
+    /// every clause being optional, and desugars it into
+    ///
+    /// ```text
+    /// { initializer; while (condition) body }
+    /// ```
+    ///
+    /// with the increment kept apart in the loop, so `continue` still runs it.
+    /// A missing condition becomes a `true` spanning the `;` where it would
+    /// have been.
+    fn for_statement(&mut self) -> Result<ast::Statement> {
+        let open = self.expect(Tk::LeftParen, Some(self.prev))?;
+
+        let initializer = match self.peek() {
+            Some(Token {
+                tipo: Tk::Semicolon,
+                ..
+            }) => {
+                self.bump();
+                None
+            }
+            Some(Token { tipo: Tk::Var, .. }) => {
+                self.bump();
+                Some(self.var_declaration()?)
+            }
+            _ => {
+                let expr = self.assignment()?;
+                self.semicolon()?;
+                Some(ast::Statement::Expression(expr))
+            }
+        };
+
+        let condition = match self.peek() {
+            Some(Token {
+                tipo: Tk::Semicolon,
+                span,
+            }) => ast::Expression {
+                span,
+                item: ast::ExpressionItem::Bool(true),
+            },
+            _ => self.assignment()?,
+        };
+        self.warn_assignment_condition(&condition);
+        self.semicolon()?;
+
+        let increment = match self.peek() {
+            Some(Token {
+                tipo: Tk::RightParen,
+                ..
+            }) => None,
+            _ => Some(self.assignment()?),
+        };
+        self.expect(Tk::RightParen, Some(open))?;
+
+        let mut body = ast::Statement::While {
+            condition,
+            body: Box::new(self.statement()?),
+            increment,
+        };
+        if let Some(initializer) = initializer {
+            body = ast::Statement::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    /// Consumes a token of kind `tipo`, which `because` requires. The error
+    /// points at the token found instead, or right after the last one when
+    /// the tokens run out.
+    fn expect(&mut self, tipo: TokenKind, because: Option<Token>) -> Result<Token> {
+        if let Some(token) = self.consume_if(tipo) {
+            return Ok(token);
+        }
+
+        let token = self.peek();
+        let end = self.prev.span.end;
+        Err(Error {
+            span: token.map_or(Span::from(end..end), |t| t.span),
+            kind: ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
+                because,
+                expected: vec![tipo],
+                found: token.map_or(Tk::Eof, |t| t.tipo),
+            }),
+        })
+    }
+
+    /// Consumes the next token if it is of kind `tipo`.
+    fn consume_if(&mut self, tipo: TokenKind) -> Option<Token> {
+        let token = self.peek().filter(|t| t.tipo == tipo)?;
+        self.bump();
+        Some(token)
+    }
+
+    /// Parses the statements after the `{` at `open` up to its `}`. A
+    /// statement that fails to parse is recorded and skipped, so the rest of
+    /// the block is still checked.
+    fn block(&mut self, open: Span) -> Result<ast::Statement> {
+        self.block_statements(open).map(ast::Statement::Block)
+    }
+
+    /// The statements of a [`block`](Self::block), without the scope.
+    fn block_statements(&mut self, open: Span) -> Result<Vec<ast::Statement>> {
+        let mut statements = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Error {
+                        span: open,
+                        kind: ErrorKind::UnclosedBlock,
+                    })
+                }
+                Some(Token {
+                    tipo: Tk::RightBrace,
+                    ..
+                }) => {
+                    self.bump();
+                    return Ok(statements);
+                }
+                Some(_) => match self.declaration() {
+                    Ok(statement) => statements.push(statement),
+                    Err(err) if err.aborts() => return Err(err),
+                    Err(err) => {
+                        self.errors.push(err);
+                        self.synchronize();
+                    }
+                },
+            }
+        }
+    }
+
+    /// Skips the tokens left of a statement that failed to parse: up to and
+    /// including the next `;`, or up to the next keyword that starts a
+    /// statement or the `}` closing the enclosing block.
+    fn synchronize(&mut self) {
+        while let Some(Token { tipo, .. }) = self.peek() {
+            match tipo {
+                Tk::Semicolon => {
+                    self.bump();
+                    return;
+                }
+                Tk::RightBrace
+                | Tk::Class
+                | Tk::Fun
+                | Tk::Var
+                | Tk::For
+                | Tk::If
+                | Tk::While
+                | Tk::Print
+                | Tk::Return => return,
+                _ => self.bump(),
+            }
+        }
+    }
+
+    /// Parses the expression that `keyword` requires, blaming `keyword` when
+    /// it is missing altogether.
+    fn expression_after(&mut self, keyword: Token) -> Result<ast::Expression> {
+        let first = self.peek();
+
+        self.expression().map_err(|mut err| {
+            if first.is_none_or(|t| t.span == err.span)
+                && let ErrorKind::UnexpectedTokenKind(unexpected) = &mut err.kind
+            {
+                unexpected.because = Some(keyword);
+            }
+            err
+        })
+    }
+
+    /// Consumes the `;` ending a statement. When it is missing the error
+    /// points right after the last token of the statement.
+    fn semicolon(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(Token {
+                tipo: Tk::Semicolon,
+                ..
+            }) => {
+                self.bump();
+                Ok(())
+            }
+            token => {
+                let end = self.prev_span().map_or(0, |span| span.end);
+
+                Err(Error {
+                    span: Span::from(end..end),
+                    kind: ErrorKind::MissingSemicolon {
+                        found: token.map_or(Tk::Eof, |t| t.tipo),
+                    },
+                })
+            }
+        }
+    }
+
+    /// Parses a single expression, as typed in the REPL.
+    pub fn parse(&mut self) -> Result<ast::Expression> {
+        let expr = self.expression();
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
+        expr
+    }
+
+    /// Parses exactly one expression, failing on any token left after it.
+    /// On failure the parser is rewound to where it started, with none of
+    /// the errors recorded on the way, so the same tokens can still be
+    /// parsed with [`Parser::parse_program`].
+    pub fn parse_expression(&mut self) -> Result<ast::Expression> {
+        let checkpoint = self.checkpoint();
+        let expr = self.expression().and_then(|expr| match self.peek() {
+            None => Ok(expr),
+            Some(token) => Err(Error {
+                span: token.span,
+                kind: ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
+                    because: None,
+                    expected: vec![Tk::Eof],
+                    found: token.tipo,
+                }),
+            }),
+        });
+        let expr = match self.errors.len() > checkpoint.errors {
+            true => Err(self.errors.remove(checkpoint.errors)),
+            false => expr,
+        };
+
+        if expr.is_err() {
+            self.rewind(checkpoint);
+        }
+        expr
+    }
+}
+
+impl Parser<'_> {
+    /// Marks the current position, so whatever is parsed after it can be
+    /// undone with [`Parser::rewind`].
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            cursor: self.cursor,
+            prev: self.prev,
+            errors: self.errors.len(),
+            warnings: self.warnings.len(),
+        }
+    }
+
+    /// Goes back to `checkpoint`, forgetting the tokens consumed and the
+    /// errors and warnings found since.
+    fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.cursor = checkpoint.cursor;
+        self.prev = checkpoint.prev;
+        self.errors.truncate(checkpoint.errors);
+        self.warnings.truncate(checkpoint.warnings);
+    }
+
+    fn bump_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.bump();
+        }
+    }
+
+    fn bump(&mut self) {
+        self.prev = self.tokens[self.cursor];
+        self.cursor += 1;
+    }
+
+    fn prev_span(&self) -> Option<Span> {
+        self.tokens.get(self.cursor.checked_sub(1)?).map(|s| s.span)
+    }
+
+    fn advance_n<const N: usize>(&mut self) -> Option<[Token; N]> {
+        let tokens = *self.next_chunk::<N>()?;
+        self.bump_n(N);
+        Some(tokens)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let [token] = self.advance_n::<1>()?;
+        Some(token)
+    }
+
+    ///
+    /// ```ignore
+    /// let next3: Option<&[Token; 3]> = parser.next_chunk::<3>();
+    /// ```
+    fn next_chunk<const N: usize>(&self) -> Option<&[Token; N]> {
+        self.tokens[self.cursor..].first_chunk::<N>()
+    }
+
+    fn lookup_n(&self, n: usize) -> Option<Token> {
+        self.tokens.get(self.cursor + n - 1).copied()
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.lookup_n(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorKind, Parser, Token, TokenKind, UnexpectedTokenKind, WarningKind};
+    use crate::{
+        ast::{
+            self, BinaryKind, Expression, ExpressionItem, Identifier, LogicalKind, Program,
+            Statement, UnaryKind,
+        },
+        scanner::Scanner,
+        span::Span,
+    };
+
+    fn parse(source: &str) -> Expression {
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        Parser::new(&tokens, source)
+            .parse()
+            .expect("It's guaranteed to parse")
+    }
+
+    /// Sets every span in `expr` to the default one, so trees parsed from
+    /// differently laid out sources compare equal.
+    fn erase_spans(expr: &mut Expression) {
+        let erase_name = |name: &mut Identifier| name.span = Span::default();
+        expr.span = Span::default();
+        match &mut expr.item {
+            ExpressionItem::Binary(lhs, rhs, _, operator) => {
+                *operator = Span::default();
+                erase_spans(lhs);
+                erase_spans(rhs);
+            }
+            ExpressionItem::Logical(lhs, rhs, _) => {
+                erase_spans(lhs);
+                erase_spans(rhs);
+            }
+            ExpressionItem::Unary(expr, _) | ExpressionItem::Grouping(expr) => erase_spans(expr),
+            ExpressionItem::Call {
+                callee,
+                paren_span,
+                args,
+            } => {
+                erase_spans(callee);
+                *paren_span = Span::default();
+                args.iter_mut().for_each(erase_spans);
+            }
+            ExpressionItem::Assign { name, value } => {
+                erase_name(name);
+                erase_spans(value);
+            }
+            ExpressionItem::Get { object, name } => {
+                erase_spans(object);
+                erase_name(name);
+            }
+            ExpressionItem::Set {
+                object,
+                name,
+                value,
+            } => {
+                erase_spans(object);
+                erase_name(name);
+                erase_spans(value);
+            }
+            ExpressionItem::Comma(exprs) => exprs.iter_mut().for_each(erase_spans),
+            ExpressionItem::Super {
+                keyword_span,
+                method,
+            } => {
+                *keyword_span = Span::default();
+                erase_name(method);
+            }
+            ExpressionItem::Lambda { params, body } => {
+                params.iter_mut().for_each(erase_name);
+                body.iter_mut().for_each(erase_statement_spans);
+            }
+            ExpressionItem::Number(_)
+            | ExpressionItem::String(_)
+            | ExpressionItem::Bool(_)
+            | ExpressionItem::Nil
+            | ExpressionItem::Variable(_)
+            | ExpressionItem::This => {}
+        }
+    }
+
+    /// See [`erase_spans`].
+    fn erase_statement_spans(statement: &mut Statement) {
+        let erase_name = |name: &mut Identifier| name.span = Span::default();
+        let erase_function = |function: &mut ast::Function| {
+            erase_name(&mut function.name);
+            function.params.iter_mut().for_each(erase_name);
+            function.body.iter_mut().for_each(erase_statement_spans);
+        };
+        match statement {
+            Statement::Expression(expr) => erase_spans(expr),
+            Statement::Print(values) => values.iter_mut().for_each(erase_spans),
+            Statement::Var { name, initializer } => {
+                erase_name(name);
+                initializer.iter_mut().for_each(erase_spans);
+            }
+            Statement::Block(statements) => statements.iter_mut().for_each(erase_statement_spans),
+            Statement::Function(function) => erase_function(function),
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                erase_name(name);
+                superclass.iter_mut().for_each(erase_name);
+                methods.iter_mut().for_each(erase_function);
+            }
+            Statement::Return {
+                keyword_span,
+                value,
+            } => {
+                *keyword_span = Span::default();
+                value.iter_mut().for_each(erase_spans);
+            }
+            Statement::Break(keyword_span) | Statement::Continue(keyword_span) => {
+                *keyword_span = Span::default()
+            }
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => {
+                erase_spans(condition);
+                erase_statement_spans(body);
+                increment.iter_mut().for_each(erase_spans);
+            }
+        }
+    }
+
+    #[test]
+    fn unexpected_token_message() {
+        let source = ")";
+        let tokens: Vec<_> = Scanner::new(source)
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, source)
+            .parse()
+            .expect_err("')' is not an expression");
+
+        assert_eq!(
+            err.kind.to_string(),
+            "expected number, 'true', 'false', string, 'nil' or identifier, found ')'"
+        );
+    }
+
+    #[test]
+    fn unexpected_token_because() {
+        let token = |tipo, at: usize| Token {
+            tipo,
+            span: Span::from(at..at + 1),
+        };
+        let message = |because, expected| {
+            ErrorKind::UnexpectedTokenKind(UnexpectedTokenKind {
+                because,
+                expected,
+                found: TokenKind::RightParen,
+            })
+            .to_string()
+        };
+
+        assert_eq!(
+            message(Some(token(TokenKind::Minus, 0)), vec![TokenKind::Number]),
+            "expected number after '-', found ')'"
+        );
+        assert_eq!(
+            message(None, vec![TokenKind::Number]),
+            "expected number, found ')'"
+        );
+        assert_eq!(
+            message(
+                Some(token(TokenKind::Var, 0)),
+                vec![TokenKind::Identifier, TokenKind::Equal]
+            ),
+            "expected identifier or '=' after 'var', found ')'"
+        );
+        assert_eq!(
+            message(
+                Some(token(TokenKind::LeftParen, 0)),
+                vec![TokenKind::RightParen]
+            ),
+            "expected ')' to close '(', found ')'"
+        );
+
+        // The token is kept to point at it
+        let Err(err) = parse_program("print (1 + 2;") else {
+            panic!("The group is never closed");
+        };
+        let ErrorKind::UnexpectedTokenKind(err) = err.kind else {
+            panic!("Expected an unexpected token");
+        };
+        assert_eq!(err.because, Some(token(TokenKind::LeftParen, 6)));
+    }
+
+    #[test]
+    fn parse_power_right_associative() {
+        assert_eq!(ast::print(&parse("2 ** 3 ** 2")), "(** 2 (** 3 2))");
+        assert_eq!(ast::print(&parse("2 * 3 ** 2")), "(* 2 (** 3 2))");
+    }
+
+    #[test]
+    fn parse_power_with_unary_minus() {
+        assert_eq!(ast::print(&parse("-2 ** 2")), "(- (** 2 2))");
+        assert_eq!(ast::print(&parse("2 ** -1")), "(** 2 (- 1))");
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for source in [
+            "1 + 2 * 3",
+            "1 - 2 - 3",
+            "!true",
+            "\"hi\"",
+            "-1 + 2 >= 3 == false",
+            "2 ** 3 ** 2",
+            "-2 ** -2",
+            "1 | 2 ^ 3 & 4 << 5",
+            "(1 + 2) * 3",
+            "a = b = 1 + 2",
+            "a or b and c",
+            "(a or b) and c",
+            "f(1, 2)(g(3))",
+            "-f() ** 2",
+            "-(2 - 3)",
+            "a = 1, f((b, c))",
+            "fun (a, b) { return a + b; }(1, 2)",
+            "f(fun () {}, fun (x) { var y = x; while (y) { print y; y = nil; } })",
+            "a % 2 * 3 % 4",
+            "a.b.c = super.d(this)",
+        ] {
+            let mut expr = parse(source);
+            let printed = expr.to_string();
+            assert_eq!(printed, source);
+
+            let mut reparsed = parse(&printed);
+            erase_spans(&mut expr);
+            erase_spans(&mut reparsed);
+            assert_eq!(reparsed, expr);
+        }
+    }
+
+    #[test]
+    fn display_adds_only_needed_parens() {
+        // A desugared `+=` has no group around its operand, the printed
+        // source needs one
+        for (source, printed) in [
+            ("((1 + 2)) * 3", "((1 + 2)) * 3"),
+            ("a += 1 + 2", "a = a + (1 + 2)"),
+            ("x -= y", "x = x - y"),
+            ("1.50 + 1e3", "1.5 + 1000"),
+        ] {
+            assert_eq!(parse(source).to_string(), printed, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn statement_display_round_trip() {
+        for source in [
+            "var a; var b = 1;",
+            "print a, b;",
+            "{} { a; { b; } }",
+            "fun f(a, b) { return; } fun g() { return f; }",
+            "class A < B { init(x) { this.x = x; } get() { return super.get(); } }",
+            "while (true) { break; continue; }",
+            "for (; i < 3; i = i + 1) print i;",
+        ] {
+            let mut program = parse_program(source).expect("It's valid");
+            let printed: Vec<_> = program.statements.iter().map(|s| s.to_string()).collect();
+            let printed = printed.join(" ");
+            assert_eq!(printed, source);
+
+            let mut reparsed = parse_program(&printed).expect("It's still valid");
+            for statement in program
+                .statements
+                .iter_mut()
+                .chain(&mut reparsed.statements)
+            {
+                erase_statement_spans(statement);
+            }
+            assert_eq!(reparsed, program);
+        }
+    }
+
+    #[test]
+    fn parse_double_bang() {
+        let res = parse("!!true");
+
+        assert_eq!(
+            res,
+            Expression {
+                span: Span::from(0..6),
+                item: ExpressionItem::Unary(
+                    Box::new(Expression {
+                        span: Span::from(1..6),
+                        item: ExpressionItem::Unary(
+                            Box::new(Expression {
+                                span: Span::from(2..6),
+                                item: ExpressionItem::Bool(true)
+                            }),
+                            UnaryKind::Bang
+                        )
+                    }),
+                    UnaryKind::Bang
+                )
+            }
+        )
+    }
+
+    #[test]
+    fn parse_double_minus() {
+        let res = parse("--5");
+
+        assert_eq!(ast::print(&res), "(- (- 5))");
+        assert_eq!(res.span, Span::from(0..3));
+    }
+
+    /// Parses `source` as a program, keeping only the first error.
+    fn parse_program(source: &str) -> super::Result<Program> {
+        parse_program_errors(source).map_err(|mut errors| errors.remove(0))
+    }
+
+    fn parse_program_errors(source: &str) -> Result<Program, Vec<super::Error>> {
+        match parse_partial(source) {
+            (program, errors) if errors.is_empty() => Ok(program),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    fn parse_partial(source: &str) -> (Program, Vec<super::Error>) {
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        Parser::new(&tokens, source).parse_partial()
+    }
+
+    #[test]
+    fn parse_two_statements() {
+        let program = parse_program("1 + 2;\n\"hi\";").expect("It's valid");
+
+        assert_eq!(
+            program.statements,
+            vec![
+                Statement::Expression(parse("1 + 2")),
+                Statement::Expression(Expression {
+                    span: Span::from(7..11),
+                    item: ExpressionItem::String("hi".to_string())
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_missing_semicolon() {
+        let err = parse_program("1 + 2;\n3 4;").expect_err("The `;` after 3 is missing");
+
+        assert_eq!(err.span, Span::from(8..8));
+        assert_eq!(err.kind.to_string(), "expected `;` after expression");
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Number
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_missing_semicolon_at_eof() {
+        let err = parse_program("1 + 2").expect_err("The `;` is missing");
+
+        assert_eq!(err.span, Span::from(5..5));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Eof
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_empty_program() {
+        assert_eq!(
+            parse_program("  // nothing here\n").expect("It's valid"),
+            Program::default()
+        );
+    }
+
+    #[test]
+    fn unfinished_input_is_eof() {
+        for source in ["(1 +", "1 *", "-", "(1"] {
+            let tokens: Vec<_> = Scanner::new(source)
+                .significant()
+                .map(|t| t.expect("It's guaranteed to be valid"))
+                .collect();
+
+            let err = Parser::new(&tokens, source)
+                .parse()
+                .expect_err("The input is unfinished");
+            assert!(err.is_eof(), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn parse_print() {
+        let program = parse_program("print \"hi\";").expect("It's valid");
+
+        assert_eq!(
+            program.statements,
+            vec![Statement::Print(vec![Expression {
+                span: Span::from(6..10),
+                item: ExpressionItem::String("hi".to_string())
+            }])]
+        );
+    }
+
+    #[test]
+    fn parse_print_many() {
+        let program = parse_program("print 1, \"x\", nil;\nprint (1, 2);").expect("It's valid");
+
+        assert_eq!(
+            ast::print_program(&program),
+            "(print 1 \"x\" nil)\n(print (group (comma 1 2)))\n"
+        );
+    }
+
+    #[test]
+    fn parse_print_without_expression() {
+        let err = parse_program("print;").expect_err("print needs an expression");
+
+        assert_eq!(err.span, Span::from(5..6));
+        match err.kind {
+            ErrorKind::UnexpectedTokenKind(err) => {
+                assert_eq!(err.because.map(|t| t.tipo), Some(TokenKind::Print));
+                assert_eq!(err.found, TokenKind::Semicolon);
+            }
+            kind => panic!("Unexpected error {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_print_missing_semicolon() {
+        let err = parse_program("print 1").expect_err("The `;` is missing");
+
+        assert_eq!(err.span, Span::from(7..7));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Eof
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_var_declaration() {
+        let program = parse_program("var x;\nvar answer = 40 + 2;").expect("It's valid");
+
+        assert_eq!(
+            program.statements,
+            vec![
+                Statement::Var {
+                    name: Identifier {
+                        span: Span::from(4..5),
+                        name: "x".to_string()
+                    },
+                    initializer: None
+                },
+                Statement::Var {
+                    name: Identifier {
+                        span: Span::from(11..17),
+                        name: "answer".to_string()
+                    },
+                    initializer: Some(Expression {
+                        span: Span::from(20..26),
+                        item: ExpressionItem::Binary(
+                            Box::new(Expression {
+                                span: Span::from(20..22),
+                                item: ExpressionItem::Number(40.0)
+                            }),
+                            Box::new(Expression {
+                                span: Span::from(25..26),
+                                item: ExpressionItem::Number(2.0)
+                            }),
+                            BinaryKind::Plus,
+                            Span::from(23..24),
+                        )
+                    })
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_var_without_identifier() {
+        let err = parse_program("var 1 = 2;").expect_err("1 is not a name");
+
+        assert_eq!(err.span, Span::from(4..5));
+        match err.kind {
+            ErrorKind::UnexpectedTokenKind(err) => {
+                assert_eq!(err.because.map(|t| t.tipo), Some(TokenKind::Var));
+                assert_eq!(err.expected, vec![TokenKind::Identifier]);
+                assert_eq!(err.found, TokenKind::Number);
+            }
+            kind => panic!("Unexpected error {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_var_missing_semicolon() {
+        let err = parse_program("var x = 1\nprint x;").expect_err("The `;` is missing");
+
+        assert_eq!(err.span, Span::from(9..9));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Print
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_variables() {
+        let res = parse("foo + bar");
+
+        assert_eq!(
+            res,
+            Expression {
+                span: Span::from(0..9),
+                item: ExpressionItem::Binary(
+                    Box::new(Expression {
+                        span: Span::from(0..3),
+                        item: ExpressionItem::Variable("foo".to_string())
+                    }),
+                    Box::new(Expression {
+                        span: Span::from(6..9),
+                        item: ExpressionItem::Variable("bar".to_string())
+                    }),
+                    BinaryKind::Plus,
+                    Span::from(4..5),
+                )
+            }
+        )
+    }
+
+    #[test]
+    fn parse_keyword_is_not_a_variable() {
+        let source = "class";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, source)
+            .parse()
+            .expect_err("class is reserved");
+
+        assert_eq!(err.span, Span::from(0..5));
+        match err.kind {
+            ErrorKind::UnexpectedTokenKind(err) => assert_eq!(err.found, TokenKind::Class),
+            kind => panic!("Unexpected error {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_assignment_is_right_associative() {
+        let res = parse("a = b = 3");
+
+        assert_eq!(
+            res,
+            Expression {
+                span: Span::from(0..9),
+                item: ExpressionItem::Assign {
+                    name: Identifier {
+                        span: Span::from(0..1),
+                        name: "a".to_string()
+                    },
+                    value: Box::new(Expression {
+                        span: Span::from(4..9),
+                        item: ExpressionItem::Assign {
+                            name: Identifier {
+                                span: Span::from(4..5),
+                                name: "b".to_string()
+                            },
+                            value: Box::new(Expression {
+                                span: Span::from(8..9),
+                                item: ExpressionItem::Number(3.0)
+                            })
+                        }
+                    })
+                }
+            }
+        )
+    }
+
+    #[test]
+    fn parse_invalid_assignment_target() {
+        for (source, target) in [
+            ("(a) = 3", Span::from(0..3)),
+            ("a + b = 3", Span::from(0..5)),
+            ("a.b += 3", Span::from(0..3)),
+            ("(a) -= 3", Span::from(0..3)),
+            ("1 %= 3", Span::from(0..1)),
+        ] {
+            let tokens: Vec<_> = Scanner::new(source)
+                .significant()
+                .map(|t| t.expect("It's guaranteed to be valid"))
+                .collect();
+
+            let err = Parser::new(&tokens, source)
+                .parse()
+                .expect_err("The target is not a variable");
+
+            assert_eq!(err.span, target, "{source:?}");
+            assert_eq!(err.kind.to_string(), "invalid assignment target");
+        }
+    }
+
+    #[test]
+    fn parse_compound_assignment() {
+        for (source, printed) in [
+            ("a += 1", "(= a (+ a 1))"),
+            ("a -= 1", "(= a (- a 1))"),
+            ("a *= 1", "(= a (* a 1))"),
+            ("a /= 1", "(= a (/ a 1))"),
+            ("a %= 1", "(= a (% a 1))"),
+            ("a += b *= 2 + 1", "(= a (+ a (= b (* b (+ 2 1)))))"),
+        ] {
+            let expr = parse(source);
+            assert_eq!(ast::print(&expr), printed);
+            assert_eq!(expr.span, Span::from(0..source.len()));
+        }
+
+        assert_eq!(ast::print(&parse("a % 2 * 3")), "(* (% a 2) 3)");
+    }
+
+    #[test]
+    fn parse_nested_blocks() {
+        let program = parse_program("{ var a = 1; { print a; } }").expect("It's valid");
+
+        assert_eq!(
+            ast::print_program(&program),
+            "(block (var a 1) (block (print a)))\n"
+        );
+    }
+
+    #[test]
+    fn parse_empty_block() {
+        let program = parse_program("{}").expect("It's valid");
+
+        assert_eq!(program.statements, vec![Statement::Block(Vec::new())]);
+    }
+
+    #[test]
+    fn parse_unclosed_block() {
+        let err = parse_program("print 0;\n{ print 1;\n{ print 2; }").expect_err("Never closed");
+
+        assert_eq!(err.span, Span::from(9..10));
+        assert_eq!(err.kind.to_string(), "this `{` is never closed");
+        assert!(err.is_eof());
+    }
+
+    #[test]
+    fn parse_block_skips_bad_statement() {
+        let (program, errors) = parse_partial("{ var 1; print 2; }");
+
+        assert_eq!(ast::print_program(&program), "(block (print 2))\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::from(6..7));
+    }
+
+    #[test]
+    fn parse_while() {
+        let program = parse_program("while (a < 3) a = a + 1;").expect("It's valid");
+
+        assert_eq!(
+            ast::print_program(&program),
+            "(while (< a 3) (= a (+ a 1)))\n"
+        );
+    }
+
+    #[test]
+    fn parse_for_without_clauses() {
+        let program = parse_program("for (;;) print 1;").expect("It's valid");
+
+        assert_eq!(
+            program.statements,
+            vec![Statement::While {
+                condition: Expression {
+                    span: Span::from(6..7),
+                    item: ExpressionItem::Bool(true)
+                },
+                body: Box::new(Statement::Print(vec![Expression {
+                    span: Span::from(15..16),
+                    item: ExpressionItem::Number(1.0)
+                }])),
+                increment: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_for_desugars_to_while() {
+        let program =
+            parse_program("for (var i = 0; i < 3; i = i + 1) print i;").expect("It's valid");
+
+        assert_eq!(
+            ast::print_program(&program),
+            "(block (var i 0) (while (< i 3) (print i) (= i (+ i 1))))\n"
+        );
+
+        let Statement::Block(outer) = &program.statements[0] else {
+            panic!("The initializer is scoped to a block");
+        };
+        let Statement::While {
+            condition,
+            increment: Some(increment),
+            ..
+        } = &outer[1]
+        else {
+            panic!("The loop follows the initializer");
+        };
+        assert_eq!(condition.span, Span::from(16..21));
+        assert_eq!(increment.span, Span::from(23..32));
+    }
+
+    #[test]
+    fn parse_for_with_expression_initializer() {
+        let program = parse_program("for (i = 0; i < 3;) {}").expect("It's valid");
+
+        assert_eq!(
+            ast::print_program(&program),
+            "(block (= i 0) (while (< i 3) (block)))\n"
+        );
+    }
+
+    #[test]
+    fn parse_for_missing_paren() {
+        let err = parse_program("for ;;) print 1;").expect_err("The `(` is missing");
+
+        assert_eq!(err.span, Span::from(4..5));
+        match err.kind {
+            ErrorKind::UnexpectedTokenKind(err) => {
+                assert_eq!(err.because.map(|t| t.tipo), Some(TokenKind::For));
+                assert_eq!(err.expected, vec![TokenKind::LeftParen]);
+            }
+            kind => panic!("Unexpected error {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_or_binds_looser_than_and() {
+        let res = parse("a or b and c");
+
+        assert_eq!(ast::print(&res), "(or a (and b c))");
+        assert_eq!(res.span, Span::from(0..12));
+    }
+
+    #[test]
+    fn parse_and_is_left_associative() {
+        let res = parse("a and b and c");
+
+        let ExpressionItem::Logical(lhs, rhs, LogicalKind::And) = &res.item else {
+            panic!("Expected an and, found {res:?}");
+        };
+        assert_eq!(ast::print(lhs), "(and a b)");
+        assert_eq!(lhs.span, Span::from(0..7));
+        assert_eq!(rhs.span, Span::from(12..13));
+    }
+
+    #[test]
+    fn parse_logical_below_equality() {
+        assert_eq!(ast::print(&parse("a == b or c")), "(or (== a b) c)");
+        assert_eq!(ast::print(&parse("x = a or b")), "(= x (or a b))");
+    }
+
+    #[test]
+    fn parse_calls() {
+        let res = parse("f(1, g(2))(x)");
+
+        assert_eq!(ast::print(&res), "(call (call f 1 (call g 2)) x)");
+        assert_eq!(res.span, Span::from(0..13));
+
+        let ExpressionItem::Call {
+            callee, paren_span, ..
+        } = &res.item
+        else {
+            panic!("Expected a call, found {res:?}");
+        };
+        assert_eq!(*paren_span, Span::from(12..13));
+        let ExpressionItem::Call { paren_span, .. } = &callee.item else {
+            panic!("Expected a call, found {callee:?}");
+        };
+        assert_eq!(*paren_span, Span::from(9..10));
+    }
+
+    #[test]
+    fn parse_chained_calls() {
+        let res = parse("f(1, 2)");
+        assert_eq!(ast::print(&res), "(call f 1 2)");
+        assert_eq!(res.span, Span::from(0..7));
+
+        let res = parse("g()(3)");
+        assert_eq!(ast::print(&res), "(call (call g) 3)");
+        assert_eq!(res.span, Span::from(0..6));
+        let ExpressionItem::Call { callee, .. } = &res.item else {
+            panic!("Expected a call, found {res:?}");
+        };
+        assert_eq!(callee.span, Span::from(0..3));
+    }
+
+    #[test]
+    fn parse_call_binds_tighter_than_unary() {
+        assert_eq!(ast::print(&parse("-f()")), "(- (call f))");
+        assert_eq!(ast::print(&parse("f() ** 2")), "(** (call f) 2)");
+    }
+
+    #[test]
+    fn parse_call_missing_paren() {
+        let source = "f(1, 2;";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, source)
+            .parse()
+            .expect_err("The call is never closed");
+
+        assert_eq!(err.span, Span::from(6..7));
+        assert_eq!(err.kind.to_string(), "expected ')' to close '(', found ';'");
+    }
+
+    #[test]
+    fn parse_too_many_arguments() {
+        let args = vec!["1"; super::MAX_ARGUMENTS + 1].join(", ");
+        let source = format!("f({args})");
+        let tokens: Vec<_> = Scanner::new(&source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, &source)
+            .parse()
+            .expect_err("There is one argument too many");
+
+        assert_eq!(err.span, Span::from(source.len() - 2..source.len() - 1));
+        assert_eq!(err.kind.to_string(), "can't have more than 255 arguments");
+
+        let args = vec!["1"; super::MAX_ARGUMENTS].join(", ");
+        assert!(matches!(
+            parse(&format!("f({args})")).item,
+            ExpressionItem::Call { .. }
+        ));
+
+        // The call is still parsed, and so is what comes after it
+        let (program, errors) = parse_partial(&format!("{source};\nprint 1;"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.statements.len(), 2);
+        let Statement::Expression(Expression {
+            item: ExpressionItem::Call { args, .. },
+            ..
+        }) = &program.statements[0]
+        else {
+            panic!("Expected a call");
+        };
+        assert_eq!(args.len(), super::MAX_ARGUMENTS + 1);
+    }
+
+    #[test]
+    fn parse_return() {
+        let program = parse_program("return;\nreturn 1 + 2;").expect("It's valid");
+
+        assert_eq!(
+            program.statements[0],
+            Statement::Return {
+                keyword_span: Span::from(0..6),
+                value: None
+            }
+        );
+        let Statement::Return {
+            keyword_span,
+            value: Some(value),
+        } = &program.statements[1]
+        else {
+            panic!("Expected a return with a value");
+        };
+        assert_eq!(*keyword_span, Span::from(8..14));
+        assert_eq!(value.span, Span::from(15..20));
+    }
+
+    #[test]
+    fn parse_return_missing_semicolon() {
+        let err = parse_program("return 1 + 2\nprint 3;").expect_err("The `;` is missing");
+        assert_eq!(err.span, Span::from(12..12));
+
+        let err = parse_program("return").expect_err("The `;` is missing");
+        assert_eq!(err.span, Span::from(6..6));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Eof
+            }
+        ));
+
+        let err = parse_program("a = 1 return a;").expect_err("The `;` is missing");
+        assert_eq!(err.span, Span::from(5..5));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Return
+            }
+        ));
+
+        let err = parse_program("print return;").expect_err("`return` isn't a value");
+        assert_eq!(err.span, Span::from(6..12));
+    }
+
+    #[test]
+    fn parse_class() {
+        let program = parse_program("class Empty {}").expect("It's valid");
+        let [Statement::Class {
+            name,
+            superclass: None,
+            methods,
+        }] = &program.statements[..]
+        else {
+            panic!("Expected a single class");
+        };
+        assert_eq!(name.name, "Empty");
+        assert_eq!(name.span, Span::from(6..11));
+        assert!(methods.is_empty());
+
+        let source = "class Point {\n  init(x, y) { x; }\n  norm() { return 1; }\n}";
+        let program = parse_program(source).expect("It's valid");
+        let [Statement::Class { methods, .. }] = &program.statements[..] else {
+            panic!("Expected a single class");
+        };
+        let names: Vec<_> = methods.iter().map(|m| m.name.name.as_str()).collect();
+        assert_eq!(names, ["init", "norm"]);
+        let params: Vec<_> = methods[0].params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(params, ["x", "y"]);
+        assert!(methods[1].params.is_empty());
+        assert_eq!(methods[1].body.len(), 1);
+    }
+
+    #[test]
+    fn parse_class_inheritance() {
+        let program = parse_program("class B < A { f() {} }").expect("It's valid");
+        assert_eq!(ast::print_program(&program), "(class B < A (f ()))\n");
+        let [Statement::Class {
+            superclass: Some(superclass),
+            ..
+        }] = &program.statements[..]
+        else {
+            panic!("Expected a class with a superclass");
+        };
+        assert_eq!(superclass.span, Span::from(10..11));
+    }
+
+    #[test]
+    fn parse_class_errors() {
+        let err = parse_program("class {}").expect_err("The name is missing");
+        assert_eq!(err.span, Span::from(6..7));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected identifier after 'class', found '{'"
+        );
+
+        let err = parse_program("class A f() {}").expect_err("The `{` is missing");
+        assert_eq!(err.span, Span::from(8..9));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected '{' after identifier, found identifier"
+        );
+
+        let err = parse_program("class A {\n  f() {}\n").expect_err("The body never ends");
+        assert_eq!(err.span, Span::from(8..9));
+        assert!(matches!(err.kind, ErrorKind::UnclosedBlock));
+        assert!(err.is_eof());
+
+        let (program, errors) = parse_partial("class A { f() {} 1 + ; g() {} }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::from(17..18));
+        let [Statement::Class { methods, .. }] = &program.statements[..] else {
+            panic!("Expected a single class");
+        };
+        let names: Vec<_> = methods.iter().map(|m| m.name.name.as_str()).collect();
+        assert_eq!(names, ["f", "g"]);
+    }
+
+    #[test]
+    fn parse_property_access() {
+        let expr = parse("a.b.c");
+        assert_eq!(ast::print(&expr), "(get (get a b) c)");
+        assert_eq!(expr.span, Span::from(0..5));
+        let ExpressionItem::Get { name, .. } = &expr.item else {
+            panic!("Expected a property access");
+        };
+        assert_eq!(name.span, Span::from(4..5));
+
+        let expr = parse("a.b().c = 1");
+        assert_eq!(ast::print(&expr), "(set (call (get a b)) c 1)");
+        assert_eq!(expr.span, Span::from(0..11));
+        assert_eq!(expr.to_string(), "a.b().c = 1");
+    }
+
+    #[test]
+    fn property_name_errors() {
+        let err = parse_program("obj.").expect_err("The name is missing");
+        assert_eq!(err.span, Span::from(4..4));
+        assert_eq!(err.kind.to_string(), "expected property name after '.'");
+        assert!(err.is_eof());
+
+        let err = parse_program("obj.123;").expect_err("A number is no name");
+        assert_eq!(err.span, Span::from(4..7));
+        assert_eq!(err.kind.to_string(), "expected property name after '.'");
+        assert!(!err.is_eof());
+    }
+
+    #[test]
+    fn parse_this_and_super() {
+        let expr = parse("this.x");
+        assert_eq!(ast::print(&expr), "(get this x)");
+        let ExpressionItem::Get { object, .. } = &expr.item else {
+            panic!("Expected a property access");
+        };
+        assert_eq!(object.span, Span::from(0..4));
+
+        let expr = parse("super.init()");
+        assert_eq!(ast::print(&expr), "(call (super init))");
+        assert_eq!(expr.to_string(), "super.init()");
+        let ExpressionItem::Call { callee, .. } = &expr.item else {
+            panic!("Expected a call");
+        };
+        let ExpressionItem::Super {
+            keyword_span,
+            method,
+        } = &callee.item
+        else {
+            panic!("Expected a super access");
+        };
+        assert_eq!(*keyword_span, Span::from(0..5));
+        assert_eq!(method.span, Span::from(6..10));
+        assert_eq!(callee.span, Span::from(0..10));
+    }
+
+    #[test]
+    fn bare_super() {
+        let err = parse_program("super;").expect_err("`super` needs a method");
+        assert_eq!(err.span, Span::from(0..5));
+        assert!(matches!(err.kind, ErrorKind::BareSuper));
+    }
+
+    #[test]
+    fn parse_break_and_continue() {
+        let program =
+            parse_program("while (true) { break; }\nwhile (x) continue;").expect("It's valid");
+        assert_eq!(
+            ast::print_program(&program),
+            "(while true (block (break)))\n(while x (continue))\n"
+        );
+        let Statement::While { body, .. } = &program.statements[1] else {
+            panic!("Expected a loop");
+        };
+        assert_eq!(**body, Statement::Continue(Span::from(34..42)));
+    }
+
+    #[test]
+    fn break_needs_semicolon() {
+        let err = parse_program("break 1;").expect_err("Only `;` may follow");
+        assert_eq!(err.span, Span::from(5..5));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingSemicolon {
+                found: TokenKind::Number
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_comma() {
+        let expr = parse("1, 2, 3");
+        assert_eq!(ast::print(&expr), "(comma 1 2 3)");
+        assert_eq!(expr.span, Span::from(0..7));
+
+        let expr = parse("a = 1, b = 2");
+        assert_eq!(ast::print(&expr), "(comma (= a 1) (= b 2))");
+        assert_eq!(expr.span, Span::from(0..12));
+
+        let expr = parse("f(1, 2)");
+        assert_eq!(ast::print(&expr), "(call f 1 2)");
+        let expr = parse("f((1, 2))");
+        assert_eq!(ast::print(&expr), "(call f (group (comma 1 2)))");
+    }
+
+    #[test]
+    fn for_clauses_take_no_comma() {
+        let err = parse_program("for (i = 0, j = 0; i < 1; i = i + 1) {}")
+            .expect_err("The initializer is a single expression");
+        assert_eq!(err.span, Span::from(10..10));
+    }
+
+    #[test]
+    fn missing_left_operand() {
+        let err = parse_program("/ 2;").expect_err("`/` needs a left operand");
+        assert_eq!(err.span, Span::from(0..1));
+        assert_eq!(err.kind.to_string(), "'/' needs a left-hand operand");
+
+        let err = parse_program("!= 1").expect_err("`!=` needs a left operand");
+        assert_eq!(err.span, Span::from(0..2));
+        assert!(matches!(
+            err.kind,
+            ErrorKind::MissingLeftOperand {
+                operator: TokenKind::BangEqual
+            }
+        ));
+        assert!(!err.is_eof());
+    }
+
+    #[test]
+    fn missing_left_operand_discards_right() {
+        let source = "== 1 + 2;";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+        let mut parser = Parser::new(&tokens, source);
+
+        parser.parse().expect_err("`==` needs a left operand");
+        assert_eq!(parser.peek().map(|t| t.tipo), Some(TokenKind::Semicolon));
+    }
+
+    #[test]
+    fn synchronize_between_statements() {
+        let source = "var = 1;\nprint 2;\nprint (3;\nvar ok = 4;\n5 6\nwhile (true) print 7;";
+        let errors = parse_program_errors(source).expect_err("Three statements are broken");
+
+        let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+        assert_eq!(
+            spans,
+            vec![Span::from(4..5), Span::from(26..27), Span::from(41..41)]
+        );
+    }
+
+    #[test]
+    fn missing_expression_leaves_the_closing_brace() {
+        for (source, span) in [("{ print }", 8..9), ("fun f() { return }", 17..18)] {
+            let errors = parse_program_errors(source).expect_err("The expression is missing");
+            let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+            assert_eq!(spans, vec![Span::from(span)], "{source}");
+        }
+
+        let source = "{ var x = } print 2;";
+        let (tokens, _) = Scanner::new(source).tokenize();
+        let mut parser = Parser::new(&tokens, source);
+        let (program, errors) = parser.parse_partial();
+        let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+        assert_eq!(spans, vec![Span::from(10..11)]);
+        assert_eq!(ast::print_program(&program), "(block)\n(print 2)\n");
+    }
+
+    #[test]
+    fn synchronize_stops_before_keywords() {
+        let errors = parse_program_errors("print ) print 2; var x = ) return 1; )")
+            .expect_err("Three statements are broken");
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn parse_function() {
+        let program =
+            parse_program("fun none() {}\nfun one(a) { print a; }\nfun many(a, b, c) { a; b; }")
+                .expect("It's valid");
+        assert_eq!(
+            ast::print_program(&program),
+            "(fun none ())\n(fun one (a) (print a))\n(fun many (a b c) a b)\n"
+        );
+
+        let Statement::Function(function) = &program.statements[2] else {
+            panic!("Expected a function");
+        };
+        assert_eq!(function.name.span, Span::from(42..46));
+        let spans: Vec<_> = function.params.iter().map(|p| p.span).collect();
+        assert_eq!(
+            spans,
+            vec![Span::from(47..48), Span::from(50..51), Span::from(53..54)]
+        );
+    }
+
+    #[test]
+    fn parse_lambda() {
+        let program = parse_program("var add = fun (a, b) { return a + b; };\nfun () {}();")
+            .expect("It's valid");
+        assert_eq!(
+            ast::print_program(&program),
+            "(var add (fun (a b) (return (+ a b))))\n(call (fun ()))\n"
+        );
+
+        let Statement::Var {
+            initializer: Some(lambda),
+            ..
+        } = &program.statements[0]
+        else {
+            panic!("Expected a variable");
+        };
+        assert_eq!(lambda.span, Span::from(10..38));
+        assert!(matches!(lambda.item, ExpressionItem::Lambda { .. }));
+
+        let Statement::Expression(Expression {
+            item: ExpressionItem::Call { callee, args, .. },
+            span,
+        }) = &program.statements[1]
+        else {
+            panic!("Expected a call");
+        };
+        assert_eq!(*span, Span::from(40..51));
+        assert!(args.is_empty());
+        assert_eq!(callee.span, Span::from(40..49));
+        assert!(matches!(callee.item, ExpressionItem::Lambda { .. }));
+
+        // `fun` and a name still declare a function
+        let program = parse_program("fun f() {}").expect("It's valid");
+        assert!(matches!(program.statements[..], [Statement::Function(_)]));
+
+        let err = parse_program("fun 1").expect_err("It's neither");
+        assert_eq!(
+            err.kind.to_string(),
+            "expected '(' after 'fun', found number"
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        for (source, expected) in [
+            ("f(1, 2,);", "(call f 1 2)\n"),
+            ("f(1, 2);", "(call f 1 2)\n"),
+            ("f(1,);", "(call f 1)\n"),
+            ("fun g(a, b,) {}", "(fun g (a b))\n"),
+            ("fun g(a, b) {}", "(fun g (a b))\n"),
+            ("var h = fun (a,) {};", "(var h (fun (a)))\n"),
+        ] {
+            let program = parse_program(source).expect("It's valid");
+            assert_eq!(ast::print_program(&program), expected);
+        }
+
+        let err = parse_program("f(,);").expect_err("A lone comma is no argument");
+        assert_eq!(err.span, Span::from(2..3));
+        let err = parse_program("f(1,,2);").expect_err("An argument is missing");
+        assert_eq!(err.span, Span::from(4..5));
+        let err = parse_program("fun g(,) {}").expect_err("A lone comma is no parameter");
+        assert_eq!(err.span, Span::from(6..7));
+    }
+
+    #[test]
+    fn function_errors() {
+        let err = parse_program("fun f a) {}").expect_err("The `(` is missing");
+        assert_eq!(err.span, Span::from(6..7));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected '(' after identifier, found identifier"
+        );
+
+        let err = parse_program("fun f(a,, b) {}").expect_err("A parameter is missing");
+        assert_eq!(err.span, Span::from(8..9));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected identifier after '(', found ','"
+        );
+
+        let err = parse_program("fun f(a) print a;").expect_err("The `{` is missing");
+        assert_eq!(err.span, Span::from(9..14));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected '{' after ')', found 'print'"
+        );
+    }
+
+    #[test]
+    fn errors_in_source_order() {
+        let (program, errors) = parse_partial("print ;\nprint 1;\n{ var = 2;\nprint 3;");
+        assert_eq!(ast::print_program(&program), "(print 1)\n");
+
+        let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+        assert_eq!(
+            spans,
+            vec![Span::from(6..7), Span::from(17..18), Span::from(23..24)]
+        );
+        assert!(matches!(errors[1].kind, ErrorKind::UnclosedBlock));
+    }
+
+    #[test]
+    fn parse_strict_stops_at_first_error() {
+        let source = "print ;\nprint 1;\nvar = 2;";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, source)
+            .parse_strict()
+            .expect_err("The first statement is broken");
+        assert_eq!(err.span, Span::from(6..7));
+
+        let err = Parser::new(&tokens[3..], source)
+            .parse_strict()
+            .expect_err("The last statement is broken");
+        assert_eq!(err.span, Span::from(21..22));
+    }
+
+    #[test]
+    fn trivia_is_skipped() {
+        let source = "// a comment\nvar a = 1 +\t2; // trailing\n/// docs\nprint a;";
+        let (tokens, errors) = Scanner::new(source).scan_all();
+        assert!(errors.is_empty());
+        assert!(tokens.iter().any(|t| t.tipo.is_trivia()));
+
+        let significant: Vec<_> = tokens
+            .iter()
+            .copied()
+            .filter(|t| !t.tipo.is_trivia())
+            .collect();
+        let program = Parser::new(&tokens, source)
+            .parse_program()
+            .expect("Whitespace and comments don't get in the way");
+        assert_eq!(
+            program,
+            Parser::new(&significant, source)
+                .parse_program()
+                .expect("The same program parses")
+        );
+        assert_eq!(ast::print_program(&program), "(var a (+ 1 2))\n(print a)\n");
+    }
+
+    #[test]
+    fn recovered_errors_are_returned() {
+        // The parser doesn't print, a bad operand is handed back as an error
+        // and the expression is kept up to it
+        let (program, errors) = parse_partial("1 + );\nprint 2 * );\nprint 3;");
+
+        let spans: Vec<_> = errors.iter().map(|err| err.span).collect();
+        assert_eq!(spans, vec![Span::from(4..5), Span::from(17..18)]);
+        assert_eq!(ast::print_program(&program), "1\n(print 2)\n(print 3)\n");
+    }
+
+    #[test]
+    fn parse_expr_parent() {
+        let source = "(4)";
+        let res = parse(source);
+
+        assert_eq!(
+            res,
+            Expression {
+                span: Span::from(0..3),
+                item: ExpressionItem::Grouping(Box::new(Expression {
+                    span: Span::from(1..2),
+                    item: ExpressionItem::Number(4.0)
+                }))
+            }
+        )
+    }
+
+    #[test]
+    fn prefix_operator_spans() {
+        assert_eq!(parse("-5").span, Span::from(0..2));
+        assert_eq!(parse("- (4)").span, Span::from(0..5));
+
+        let expr = parse("1 + -x");
+        let ExpressionItem::Binary(_, rhs, ..) = &expr.item else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(rhs.span, Span::from(4..6));
+    }
+
+    #[test]
+    fn parse_grouping_consumes_paren() {
+        let expr = parse("(1 == 2) + 3");
+
+        assert_eq!(ast::print(&expr), "(+ (group (== 1 2)) 3)");
+        assert_eq!(expr.span, Span::from(0..12));
+    }
+
+    #[test]
+    fn parse_unclosed_grouping() {
+        let source = "(1 + 2";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect();
+
+        let err = Parser::new(&tokens, source)
+            .parse()
+            .expect_err("The group is never closed");
+
+        assert_eq!(err.span, Span::from(6..6));
+        assert_eq!(
+            err.kind.to_string(),
+            "expected ')' to close '(', found end of file"
+        );
+    }
+
+    #[test]
+    fn rewind_after_failed_speculation() {
+        let source = "1 < 2 < 3; (4 < 5 < 6 - ) 7;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+
+        parser.statement().expect("It's valid");
+        assert_eq!(parser.warnings().len(), 1);
+        let (cursor, prev) = (parser.cursor, parser.prev);
+        let checkpoint = parser.checkpoint();
+
+        parser.statement().expect_err("The group is broken");
+        assert_ne!(parser.cursor, cursor);
+        assert_eq!(parser.errors.len(), 1);
+        assert_eq!(parser.warnings().len(), 2);
+
+        parser.rewind(checkpoint);
+        assert_eq!(parser.cursor, cursor);
+        assert_eq!(parser.prev, prev);
+        assert!(parser.errors.is_empty());
+        assert_eq!(parser.warnings().len(), 1);
+    }
+
+    #[test]
+    fn failed_parse_expression_leaves_the_parser_as_it_was() {
+        let source = "{ 1 + ; } 2 < 3 < 4 + (5 6";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+
+        parser.statement().expect("The block recovers");
+        let (cursor, prev) = (parser.cursor, parser.prev);
+        let errors = format!("{:?}", parser.errors);
+        let warnings = format!("{:?}", parser.warnings());
+        assert_eq!(parser.errors.len(), 1);
+
+        parser
+            .parse_expression()
+            .expect_err("The group is never closed");
+        assert_eq!(parser.cursor, cursor);
+        assert_eq!(parser.prev, prev);
+        assert_eq!(format!("{:?}", parser.errors), errors);
+        assert_eq!(format!("{:?}", parser.warnings()), warnings);
+    }
+
+    #[test]
+    fn parse_expression_or_program() {
+        let source = "1 + 2";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+        let expr = parser.parse_expression().expect("It's a single expression");
+        assert_eq!(ast::print(&expr), "(+ 1 2)");
+
+        let source = "var x = 1; x = -x;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+        let err = parser
+            .parse_expression()
+            .expect_err("`var` starts a statement");
+        assert_eq!(err.span, Span::from(0..3));
+        assert_eq!(parser.cursor, 0);
+        assert!(parser.errors.is_empty());
+        let program = parser.parse_program().expect("It's a valid program");
+        assert_eq!(program.statements.len(), 2);
+
+        let source = "x = 1;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+        let err = parser.parse_expression().expect_err("The `;` is left");
+        assert_eq!(err.kind.to_string(), "expected end of file, found ';'");
+        assert_eq!(err.span, Span::from(5..6));
+        assert!(parser.parse_program().is_ok());
+    }
+
+    #[test]
+    fn unclosed_paren_label() {
+        let err = parse_program("(1 + (2 * 3;").expect_err("The inner `(` is never closed");
+        assert_eq!(err.span, Span::from(11..12));
+        assert_eq!(
+            err.labels(),
+            vec![(Span::from(5..6), "opened here".to_string())]
+        );
+
+        let err = parse_program("print 1 2;").expect_err("The `;` is missing");
+        assert!(err.labels().is_empty());
+    }
+
+    #[test]
+    fn expected_token_at_eof() {
+        // Zero-width, right after the last token, whitespace aside
+        for (source, end, expected) in [
+            ("while (true  ", 11, "')' to close '('"),
+            ("fun f(a", 7, "')' to close '('"),
+            ("class A", 7, "'{' after identifier"),
+            ("var", 3, "identifier after 'var'"),
+            ("f(1, 2", 6, "')' to close '('"),
+        ] {
+            let err = parse_program(source).expect_err("The source ends early");
+
+            assert_eq!(err.span, Span::from(end..end), "{source}");
+            assert!(err.is_eof(), "{source}");
+            assert_eq!(
+                err.kind.to_string(),
+                format!("expected {expected}, found end of file"),
+                "{source}"
+            );
+        }
+
+        // Otherwise the token found is blamed
+        let err = parse_program("while (true;").expect_err("The `)` is missing");
+        assert_eq!(err.span, Span::from(11..12));
+    }
+
+    #[test]
+    fn parse_bitwise_precedence() {
+        let source = "1 | 2 & 3 << 4";
+        let expr = parse(source);
+
+        assert_eq!(ast::print(&expr), "(| 1 (& 2 (<< 3 4)))");
+        assert_eq!(expr.span, Span::from(0..14));
+
+        let ExpressionItem::Binary(_, and, ..) = expr.item else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(and.span, Span::from(4..14));
+
+        let ExpressionItem::Binary(_, shl, ..) = and.item else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(shl.span, Span::from(8..14));
+    }
+
+    #[test]
+    fn parse_bitwise_left_associative() {
+        assert_eq!(ast::print(&parse("1 | 2 | 3")), "(| (| 1 2) 3)");
+        assert_eq!(ast::print(&parse("1 >> 2 << 3")), "(<< (>> 1 2) 3)");
+        assert_eq!(ast::print(&parse("1 < 2 ^ 3")), "(^ (< 1 2) 3)");
+    }
+
+    #[test]
+    fn parse_bitwise_like_c() {
+        assert_eq!(ast::print(&parse("a & b == c")), "(& a (== b c))");
+        assert_eq!(ast::print(&parse("a | b < c")), "(| a (< b c))");
+        assert_eq!(ast::print(&parse("1 << 2 < 3")), "(< (<< 1 2) 3)");
+        assert_eq!(
+            ast::print(&parse("a == b & c and d | e ^ f")),
+            "(and (& (== a b) c) (| d (^ e f)))"
+        );
+    }
+
+    fn scan(source: &str) -> Vec<super::Token> {
+        Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid"))
+            .collect()
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        // Test threads only get 2 MiB, too little for `MAX_DEPTH` levels
+        let deep = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| {
+                let source = "(".repeat(10_000);
+                let tokens = scan(&source);
+
+                let err = Parser::new(&tokens, &source)
+                    .parse()
+                    .expect_err("It's nested too deeply");
+                assert!(matches!(err.kind, ErrorKind::TooDeep));
+                assert!(!err.is_eof());
+                assert_eq!(err.kind.to_string(), "too deeply nested");
+
+                for source in [
+                    format!("print {}1;", "!".repeat(10_000)),
+                    format!("{}1;", "{".repeat(10_000)),
+                    "fun f() {".repeat(10_000),
+                ] {
+                    let tokens = scan(&source);
+                    let (_, errors) = Parser::new(&tokens, &source).parse_partial();
+                    assert_eq!(errors.len(), 1);
+                    assert!(matches!(errors[0].kind, ErrorKind::TooDeep));
+                }
+
+                let source = format!("{}1{}", "(".repeat(500), ")".repeat(500));
+                let tokens = scan(&source);
+                Parser::new(&tokens, &source)
+                    .parse()
+                    .expect("Ordinary nesting is fine");
+            })
+            .expect("The thread can be spawned");
+        deep.join().expect("The parser doesn't overflow its stack");
+    }
+
+    #[test]
+    fn max_depth() {
+        let source = "((1))";
+        let tokens = scan(source);
+
+        let err = Parser::new(&tokens, source)
+            .with_max_depth(2)
+            .parse()
+            .expect_err("Each group nests one level");
+        assert!(matches!(err.kind, ErrorKind::TooDeep));
+        assert_eq!(err.span, Span::from(2..3));
+
+        let expr = Parser::new(&tokens, source)
+            .with_max_depth(3)
+            .parse()
+            .expect("It's shallow enough");
+        assert_eq!(ast::print(&expr), "(group (group 1))");
+
+        let source = "{ { print 1; } }";
+        let tokens = scan(source);
+        let (_, errors) = Parser::new(&tokens, source)
+            .with_max_depth(2)
+            .parse_partial();
+        assert!(matches!(
+            errors[..],
+            [super::Error {
+                kind: ErrorKind::TooDeep,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn binary_precedence_table() {
+        // Loosest first, operators on the same row bind alike
+        let levels: &[&[&str]] = &[
+            &["or"],
+            &["and"],
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", "<=", ">", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/"],
+        ];
+
+        for (i, lhs_ops) in levels.iter().enumerate() {
+            for (j, rhs_ops) in levels.iter().enumerate() {
+                for (a, b) in lhs_ops
+                    .iter()
+                    .flat_map(|a| rhs_ops.iter().map(move |b| (a, b)))
+                {
+                    let source = format!("1 {a} 2 {b} 3");
+                    // Left-associative, the right operator only groups first
+                    // when it binds tighter
+                    let expected = if j > i {
+                        format!("({a} 1 ({b} 2 3))")
+                    } else {
+                        format!("({b} ({a} 1 2) 3)")
+                    };
+                    let expr = parse(&source);
+
+                    assert_eq!(ast::print(&expr), expected, "{source}");
+                    assert_eq!(expr.span, Span::from(0..source.len()), "{source}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_expr_number() {
+        assert_eq!(
+            parse("4"),
+            Expression {
+                span: Span::from(0..1),
+                item: ExpressionItem::Number(4.0),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_expr_binary() {
+        let group = |n: f64, at: usize| Expression {
+            span: Span::from(at..at + 3),
+            item: ExpressionItem::Grouping(Box::new(Expression {
+                span: Span::from(at + 1..at + 2),
+                item: ExpressionItem::Number(n),
+            })),
+        };
+
+        assert_eq!(
+            parse("(4) + (5)"),
+            Expression {
+                span: Span::from(0..9),
+                item: ExpressionItem::Binary(
+                    Box::new(group(4.0, 0)),
+                    Box::new(group(5.0, 6)),
+                    BinaryKind::Plus,
+                    Span::from(4..5),
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn assignment_in_condition_warning() {
+        let source = "while (x = 1) print x;\nfor (; a.b = c;) {}\nwhile ((x = 1)) {}\nx = 2;\n\
+                      while (x -= 1) x = 2;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+
+        let program = parser.parse_program().expect("It's still valid");
+        assert_eq!(
+            ast::print_program(&program),
+            "(while (= x 1) (print x))\n(while (set a b c) (block))\n\
+             (while (group (= x 1)) (block))\n(= x 2)\n(while (= x (- x 1)) (= x 2))\n"
+        );
+
+        let spans: Vec<_> = parser.warnings().iter().map(|w| w.span).collect();
+        assert_eq!(spans, vec![Span::from(9..10), Span::from(34..35)]);
+        assert!(parser
+            .warnings()
+            .iter()
+            .all(|w| matches!(w.kind, WarningKind::AssignmentInCondition { .. })));
+        assert_eq!(
+            parser.warnings()[0].labels(),
+            vec![(
+                Span::from(7..12),
+                "help: use `==` to compare, or wrap in parentheses to silence".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn inexact_number_warning() {
+        let source = "9007199254740992; 9007199254740993; 0012; 1e30; 0.1; \
+                      100000000000000000000000;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+        parser.parse_program().expect("It's still valid");
+
+        let [first, second] = parser.warnings() else {
+            panic!("Only 2 ** 53 + 1 and 10 ** 23 are inexact");
+        };
+        assert_eq!(first.span, Span::from(18..34));
+        assert_eq!(
+            first.kind.to_string(),
+            "integer literal can't be represented exactly, it becomes 9007199254740992"
+        );
+        assert_eq!(second.span, Span::from(53..77));
+        assert_eq!(
+            second.kind.to_string(),
+            "integer literal can't be represented exactly, it becomes 99999999999999991611392"
+        );
+    }
+
+    #[test]
+    fn invalid_number_token() {
+        let source = "1.2.3";
+        let tokens = [Token {
+            tipo: TokenKind::Number,
+            span: Span::from(0..5),
+        }];
+        let err = Parser::new(&tokens, source)
+            .parse()
+            .expect_err("It's no number");
+        assert!(matches!(err.kind, ErrorKind::InvalidNumber));
+        assert_eq!(err.span, Span::from(0..5));
+    }
+
+    #[test]
+    fn no_input_panics() {
+        let alphabet = ["1", ".", "e", "-", "(", ")", ";", "=", "x", "fun", "{", ","];
+        let mut sources = vec![String::new()];
+        for _ in 0..4 {
+            sources = sources
+                .iter()
+                .flat_map(|source| alphabet.iter().map(move |c| format!("{source}{c}")))
+                .collect();
+            for source in &sources {
+                let (tokens, _) = Scanner::new(source).tokenize();
+                let _ = Parser::new(&tokens, source).parse_program();
+                let _ = Parser::new(&tokens, source).parse_expression();
+            }
+        }
+    }
+
+    #[test]
+    fn chained_comparison_warning() {
+        let source = "1 < 2 < 3;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+        let program = parser.parse_program().expect("It's still valid");
+        assert_eq!(ast::print_program(&program), "(< (< 1 2) 3)\n");
+        assert_eq!(parser.warnings().len(), 1);
+
+        let source =
+            "1 < 2 < 3;\n(1 < 2) < 3;\n1 < 2 == 3 >= 4;\n1 < 2 and 2 < 3;\n1 <= 2 + 3 > 4;";
+        let tokens = scan(source);
+        let mut parser = Parser::new(&tokens, source);
+
+        let program = parser.parse_program().expect("It's still valid");
+        assert_eq!(
+            ast::print(match &program.statements[0] {
+                Statement::Expression(expr) => expr,
+                _ => panic!("Expected an expression"),
+            }),
+            "(< (< 1 2) 3)"
+        );
+
+        let spans: Vec<_> = parser.warnings().iter().map(|w| w.span).collect();
+        assert_eq!(spans, vec![Span::from(6..7), Span::from(69..70)]);
+        assert_eq!(parser.warnings().len(), 2);
+        assert!(matches!(
+            parser.warnings()[0].kind,
+            WarningKind::ChainedComparison
+        ));
+    }
+}
@@ -1,23 +1,88 @@
-use crate::span::Span;
-use std::ops::Not;
+use crate::span::{Span, BOM};
+use std::{borrow::Cow, ops::Not};
 
 pub type Tk = TokenKind;
 
 pub struct Scanner<'src> {
     cursor: Cursor<'src>,
     start: usize,
+    /// Whether the [`TokenKind::Eof`] closing the source was yielded.
+    done: bool,
 }
 
 impl<'src> Scanner<'src> {
-    pub fn new(src: &'src str) -> Scanner {
+    pub fn new(src: &'src str) -> Scanner<'src> {
         Scanner {
             cursor: Cursor::new(src),
             start: 0,
+            done: false,
         }
     }
+
+    /// Continues scanning `src` from the byte `offset`, e.g. after more text
+    /// was appended to a buffer that was already scanned up to
+    /// [`Scanner::position`]. Spans stay absolute offsets into `src`.
+    ///
+    /// The last token before `offset` may continue in the appended text (a
+    /// number, an identifier, `=` becoming `==`), so callers usually resume
+    /// from the start of that token and drop it from the old results.
+    ///
+    /// An `offset` inside a character is moved back to where it starts, one
+    /// past the end of `src` to its end.
+    pub fn resume_at(src: &'src str, offset: usize) -> Scanner<'src> {
+        let offset = (0..=offset.min(src.len()))
+            .rev()
+            .find(|&i| src.is_char_boundary(i))
+            .unwrap_or(0);
+        if offset == 0 {
+            return Scanner::new(src);
+        }
+
+        Scanner {
+            cursor: Cursor::at(src, offset),
+            start: offset,
+            done: false,
+        }
+    }
+
+    /// Byte offset of the next character to scan.
+    pub fn position(&self) -> usize {
+        self.cursor.position
+    }
+
+    /// Adapts the scanner to skip whitespace and comments, yielding only the
+    /// tokens the parser cares about along with every error.
+    pub fn significant(self) -> impl Iterator<Item = Result<Token, Error>> + 'src {
+        self.filter(|token| !matches!(token, Ok(token) if token.tipo.is_trivia()))
+    }
+
+    /// Scans the whole source into the tokens the parser cares about, with
+    /// every error found along the way kept apart.
+    pub fn tokenize(self) -> (Vec<Token>, Vec<Error>) {
+        let (mut tokens, errors) = self.scan_all();
+        tokens.retain(|token| !token.tipo.is_trivia());
+        (tokens, errors)
+    }
+
+    /// Scans the whole source, keeping every token (whitespace, comments and
+    /// doc comments included) apart from the errors found along the way.
+    pub fn scan_all(self) -> (Vec<Token>, Vec<Error>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+
+        for token in self {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        (tokens, errors)
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Error {
     pub span: Span,
     pub kind: ErrorKind,
@@ -29,19 +94,58 @@ impl Error {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ErrorKind {
     UnfinishedStr,
     UnknownToken,
-    InvalidNumber,
+    InvalidNumber { reason: NumberErrorReason },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumberErrorReason {
+    MultipleDecimalPoints,
+    MissingExponentDigits,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::UnfinishedStr => write!(f, "unfinished string"),
+            ErrorKind::UnknownToken => write!(f, "unknown token"),
+            ErrorKind::InvalidNumber { reason } => write!(f, "invalid number, {reason}"),
+        }
+    }
+}
+
+impl std::fmt::Display for NumberErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberErrorReason::MultipleDecimalPoints => {
+                write!(f, "it has more than one decimal point")
+            }
+            NumberErrorReason::MissingExponentDigits => {
+                write!(f, "the exponent has no digits")
+            }
+        }
+    }
 }
 
 impl Iterator for Scanner<'_> {
     type Item = Result<Token, Error>;
 
+    /// Yields a single [`TokenKind::Eof`], spanning nothing at the end of
+    /// the source, after every other token.
     fn next(&mut self) -> Option<Self::Item> {
-        let c = self.cursor.next()?;
-        self.start = self.cursor.position - 1;
+        self.start = self.cursor.position;
+        let Some(c) = self.cursor.next() else {
+            if self.done {
+                return None;
+            }
+            self.done = true;
+            return Some(Ok(Token::new(Tk::Eof, Span::from(self.start..self.start))));
+        };
 
         match self.parse_next(c) {
             Ok(tt) => Some(Ok(Token::new(
@@ -60,7 +164,9 @@ impl<'src> Scanner<'src> {
     fn parse_next(&mut self, c: char) -> Result<TokenKind, ErrorKind> {
         Ok(match c {
             'a'..='z' | 'A'..='Z' | '_' => self.parse_reserved().unwrap_or(Tk::Identifier),
-            '0'..='9' => self.parse_number().ok_or(ErrorKind::InvalidNumber)?,
+            '0'..='9' => self
+                .parse_number()
+                .map_err(|reason| ErrorKind::InvalidNumber { reason })?,
             ' ' | '\n' | '\t' | '\r' => self.parse_space(),
             '(' => Tk::LeftParen,
             ')' => Tk::RightParen,
@@ -68,24 +174,43 @@ impl<'src> Scanner<'src> {
             '}' => Tk::RightBrace,
             ',' => Tk::Comma,
             '.' => Tk::Dot,
-            '-' => Tk::Minus,
-            '+' => Tk::Plus,
+            '-' => self.on_match('=', |_| Tk::MinusEqual).unwrap_or(Tk::Minus),
+            '+' => self.on_match('=', |_| Tk::PlusEqual).unwrap_or(Tk::Plus),
+            '%' => self
+                .on_match('=', |_| Tk::PercentEqual)
+                .unwrap_or(Tk::Percent),
             ';' => Tk::Semicolon,
-            '*' => Tk::Star,
+            '*' => self
+                .on_match('*', |_| Tk::StarStar)
+                .or_else(|| self.on_match('=', |_| Tk::StarEqual))
+                .unwrap_or(Tk::Star),
             '!' => self.on_match('=', |_| Tk::BangEqual).unwrap_or(Tk::Bang),
             '=' => self.on_match('=', |_| Tk::EqualEqual).unwrap_or(Tk::Equal),
             '>' => self
                 .on_match('=', |_| Tk::GreaterEqual)
+                .or_else(|| self.on_match('>', |_| Tk::GreaterGreater))
                 .unwrap_or(Tk::Greater),
-            '<' => self.on_match('=', |_| Tk::LessEqual).unwrap_or(Tk::Less),
+            '<' => self
+                .on_match('=', |_| Tk::LessEqual)
+                .or_else(|| self.on_match('<', |_| Tk::LessLess))
+                .unwrap_or(Tk::Less),
+            '&' => Tk::Ampersand,
+            '|' => Tk::Pipe,
+            '^' => Tk::Caret,
             '/' => self
                 .on_match('/', |s| {
+                    let doc = s.cursor.peek() == Some('/');
                     while s.cursor.peek().unwrap_or('\n') != '\n' {
                         s.cursor.bump()
                     }
 
-                    Tk::CommentLine
+                    if doc {
+                        Tk::DocComment
+                    } else {
+                        Tk::CommentLine
+                    }
                 })
+                .or_else(|| self.on_match('=', |_| Tk::SlashEqual))
                 .unwrap_or(Tk::Slash),
             '"' => self.parse_string().ok_or(ErrorKind::UnfinishedStr)?,
             _ => return Err(ErrorKind::UnknownToken),
@@ -106,6 +231,34 @@ impl<'src> Scanner<'src> {
     }
 }
 
+/// Looks up the keyword spelled by `ident`, branching on its first bytes so
+/// most identifiers are rejected after a single comparison.
+fn keyword(ident: &str) -> Option<TokenKind> {
+    let (spelling, kind) = match ident.as_bytes() {
+        [b'a', ..] => ("and", Tk::And),
+        [b'b', ..] => ("break", Tk::Break),
+        [b'c', b'l', ..] => ("class", Tk::Class),
+        [b'c', b'o', ..] => ("continue", Tk::Continue),
+        [b'e', ..] => ("else", Tk::Else),
+        [b'f', b'a', ..] => ("false", Tk::False),
+        [b'f', b'o', ..] => ("for", Tk::For),
+        [b'f', b'u', ..] => ("fun", Tk::Fun),
+        [b'i', ..] => ("if", Tk::If),
+        [b'n', ..] => ("nil", Tk::Nil),
+        [b'o', ..] => ("or", Tk::Or),
+        [b'p', ..] => ("print", Tk::Print),
+        [b'r', ..] => ("return", Tk::Return),
+        [b's', ..] => ("super", Tk::Super),
+        [b't', b'h', ..] => ("this", Tk::This),
+        [b't', b'r', ..] => ("true", Tk::True),
+        [b'v', ..] => ("var", Tk::Var),
+        [b'w', ..] => ("while", Tk::While),
+        _ => return None,
+    };
+
+    (ident == spelling).then_some(kind)
+}
+
 impl<'src> Scanner<'src> {
     fn parse_space(&mut self) -> TokenKind {
         let empty = [' ', '\t', '\r', '\n'];
@@ -120,36 +273,17 @@ impl<'src> Scanner<'src> {
         TokenKind::Whitespace
     }
 
-    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) {
-        while predicate(self.cursor.peek().unwrap_or_default()) {
-            self.cursor.bump()
-        }
+    /// Bumps every following ASCII byte matching `predicate`.
+    fn bump_while(&mut self, predicate: impl Fn(u8) -> bool) {
+        self.cursor.bump_ascii_while(predicate)
     }
 
     fn parse_reserved(&mut self) -> Option<TokenKind> {
-        self.bump_while(|c| c.is_ascii_digit() || c.is_ascii_alphabetic() || c == '_');
-        Some(match &self.cursor.orig[self.start..self.cursor.position] {
-            "if" => Tk::If,
-            "or" => Tk::Or,
-            "and" => Tk::And,
-            "for" => Tk::For,
-            "fun" => Tk::Fun,
-            "var" => Tk::Var,
-            "nil" => Tk::Nil,
-            "else" => Tk::Else,
-            "true" => Tk::True,
-            "this" => Tk::This,
-            "class" => Tk::Class,
-            "false" => Tk::False,
-            "print" => Tk::Print,
-            "super" => Tk::Super,
-            "while" => Tk::While,
-            "return" => Tk::Return,
-            _ => return None,
-        })
+        self.bump_while(|c| c.is_ascii_alphanumeric() || c == b'_');
+        keyword(&self.cursor.orig[self.start..self.cursor.position])
     }
 
-    fn parse_number(&mut self) -> Option<TokenKind> {
+    fn parse_number(&mut self) -> Result<TokenKind, NumberErrorReason> {
         let mut punto = false;
 
         while let Some(c) = self.cursor.peek() {
@@ -157,8 +291,8 @@ impl<'src> Scanner<'src> {
             match c {
                 '0'..='9' => self.cursor.bump(),
                 '.' if nxt_is_num() && punto => {
-                    self.bump_while(|c| c.is_ascii_digit() || c == '.');
-                    return None;
+                    self.bump_malformed_number();
+                    return Err(NumberErrorReason::MultipleDecimalPoints);
                 }
                 '.' if nxt_is_num() && !punto => {
                     self.cursor.bump();
@@ -168,7 +302,29 @@ impl<'src> Scanner<'src> {
             }
         }
 
-        Some(TokenKind::Number)
+        if let Some('e' | 'E') = self.cursor.peek() {
+            self.cursor.bump();
+            if let Some('+' | '-') = self.cursor.peek()
+                && matches!(self.cursor.peek_nth(1), Some('0'..='9'))
+            {
+                self.cursor.bump();
+            }
+
+            if !matches!(self.cursor.peek(), Some('0'..='9')) {
+                self.bump_malformed_number();
+                return Err(NumberErrorReason::MissingExponentDigits);
+            }
+
+            self.bump_while(|c| c.is_ascii_digit());
+        }
+
+        Ok(TokenKind::Number)
+    }
+
+    /// Consumes whatever is left of a malformed number literal so the error
+    /// span covers all of it and scanning resumes after it.
+    fn bump_malformed_number(&mut self) {
+        self.bump_while(|c| c.is_ascii_alphanumeric() || c == b'.' || c == b'_');
     }
 
     fn parse_string(&mut self) -> Option<TokenKind> {
@@ -189,13 +345,19 @@ impl<'src> Scanner<'src> {
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
+    Ampersand,
     And,
     Bang,
     BangEqual,
+    Break,
+    Caret,
     Class,
     Comma,
+    Continue,
     CommentLine,
+    DocComment,
     Dot,
     #[default]
     Eof,
@@ -207,25 +369,35 @@ pub enum TokenKind {
     Fun,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     If,
     Identifier,
     LeftBrace,
     LeftParen,
     Less,
     LessEqual,
+    LessLess,
     Minus,
+    MinusEqual,
     Nil,
     Number,
     Or,
+    Percent,
+    PercentEqual,
+    Pipe,
     Print,
     Plus,
+    PlusEqual,
     Return,
     RightBrace,
     RightParen,
     Super,
     Semicolon,
     Slash,
+    SlashEqual,
     Star,
+    StarEqual,
+    StarStar,
     String,
     This,
     True,
@@ -234,16 +406,164 @@ pub enum TokenKind {
     Whitespace,
 }
 
+impl TokenKind {
+    /// Whitespace and comments, which the grammar never looks at.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Tk::Whitespace | Tk::CommentLine | Tk::DocComment)
+    }
+
+    /// The reserved words, which can't name a variable.
+    pub fn is_keyword(self) -> bool {
+        matches!(
+            self,
+            Tk::And
+                | Tk::Break
+                | Tk::Class
+                | Tk::Continue
+                | Tk::Else
+                | Tk::False
+                | Tk::For
+                | Tk::Fun
+                | Tk::If
+                | Tk::Nil
+                | Tk::Or
+                | Tk::Print
+                | Tk::Return
+                | Tk::Super
+                | Tk::This
+                | Tk::True
+                | Tk::Var
+                | Tk::While
+        )
+    }
+
+    /// Operators written between two operands, `and` and `or` included.
+    /// `-` is one too, even if it may also negate a single operand.
+    pub fn is_binary_operator(self) -> bool {
+        matches!(
+            self,
+            Tk::Ampersand
+                | Tk::And
+                | Tk::BangEqual
+                | Tk::Caret
+                | Tk::EqualEqual
+                | Tk::Greater
+                | Tk::GreaterEqual
+                | Tk::GreaterGreater
+                | Tk::Less
+                | Tk::LessEqual
+                | Tk::LessLess
+                | Tk::Minus
+                | Tk::Or
+                | Tk::Percent
+                | Tk::Pipe
+                | Tk::Plus
+                | Tk::Slash
+                | Tk::Star
+                | Tk::StarStar
+        )
+    }
+
+    /// The tokens a literal value is written with.
+    pub fn is_literal_start(self) -> bool {
+        matches!(
+            self,
+            Tk::Number | Tk::String | Tk::True | Tk::False | Tk::Nil
+        )
+    }
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Tk::Ampersand => "'&'",
+            Tk::And => "'and'",
+            Tk::Bang => "'!'",
+            Tk::BangEqual => "'!='",
+            Tk::Break => "'break'",
+            Tk::Caret => "'^'",
+            Tk::Class => "'class'",
+            Tk::Comma => "','",
+            Tk::Continue => "'continue'",
+            Tk::CommentLine => "comment",
+            Tk::DocComment => "doc comment",
+            Tk::Dot => "'.'",
+            Tk::Eof => "end of file",
+            Tk::Else => "'else'",
+            Tk::Equal => "'='",
+            Tk::EqualEqual => "'=='",
+            Tk::False => "'false'",
+            Tk::For => "'for'",
+            Tk::Fun => "'fun'",
+            Tk::Greater => "'>'",
+            Tk::GreaterEqual => "'>='",
+            Tk::GreaterGreater => "'>>'",
+            Tk::If => "'if'",
+            Tk::Identifier => "identifier",
+            Tk::LeftBrace => "'{'",
+            Tk::LeftParen => "'('",
+            Tk::Less => "'<'",
+            Tk::LessEqual => "'<='",
+            Tk::LessLess => "'<<'",
+            Tk::Minus => "'-'",
+            Tk::MinusEqual => "'-='",
+            Tk::Nil => "'nil'",
+            Tk::Number => "number",
+            Tk::Or => "'or'",
+            Tk::Percent => "'%'",
+            Tk::PercentEqual => "'%='",
+            Tk::Pipe => "'|'",
+            Tk::Print => "'print'",
+            Tk::Plus => "'+'",
+            Tk::PlusEqual => "'+='",
+            Tk::Return => "'return'",
+            Tk::RightBrace => "'}'",
+            Tk::RightParen => "')'",
+            Tk::Super => "'super'",
+            Tk::Semicolon => "';'",
+            Tk::Slash => "'/'",
+            Tk::SlashEqual => "'/='",
+            Tk::Star => "'*'",
+            Tk::StarEqual => "'*='",
+            Tk::StarStar => "'**'",
+            Tk::String => "string",
+            Tk::This => "'this'",
+            Tk::True => "'true'",
+            Tk::Var => "'var'",
+            Tk::While => "'while'",
+            Tk::Whitespace => "whitespace",
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
+    #[cfg_attr(feature = "serde", serde(rename = "kind"))]
     pub tipo: TokenKind,
     pub span: Span,
 }
 
-impl Token {
-    fn tipo(s: Option<Self>) -> Option<TokenKind> {
-        s.map(|t| t.tipo)
+/// Serializes `tokens` as a JSON array where each token also carries its
+/// `lexeme`, the text of `source` it spans.
+#[cfg(feature = "serde")]
+pub fn tokens_to_json(tokens: &[Token], source: &str) -> String {
+    #[derive(serde::Serialize)]
+    struct LexedToken<'a> {
+        #[serde(flatten)]
+        token: &'a Token,
+        lexeme: &'a str,
     }
+
+    let tokens: Vec<_> = tokens
+        .iter()
+        .map(|token| LexedToken {
+            token,
+            lexeme: token.lexeme(source),
+        })
+        .collect();
+
+    serde_json::to_string(&tokens).expect("Tokens are always serializable")
 }
 
 impl Default for Token {
@@ -259,6 +579,19 @@ impl Token {
     fn new(vtipo: TokenKind, span: Span) -> Self {
         Token { tipo: vtipo, span }
     }
+
+    /// The text of `source` the token spans.
+    pub fn lexeme<'src>(&self, source: &'src str) -> &'src str {
+        &source[self.span.range()]
+    }
+
+    /// The contents of a [`TokenKind::String`], without its quotes. Strings
+    /// have no escapes, so this borrows from `source` for now.
+    pub fn string_value<'src>(&self, source: &'src str) -> Cow<'src, str> {
+        let lexeme = self.lexeme(source);
+        let contents = lexeme.strip_prefix('"').unwrap_or(lexeme);
+        Cow::Borrowed(contents.strip_suffix('"').unwrap_or(contents))
+    }
 }
 struct Cursor<'src> {
     source: &'src str,
@@ -268,13 +601,28 @@ struct Cursor<'src> {
     position: usize,
 }
 impl<'src> Cursor<'src> {
-    fn new(src: &'src str) -> Cursor {
+    /// Creates a cursor over `src`, skipping a leading UTF-8 byte order mark.
+    ///
+    /// Positions are still byte offsets into `src`, so spans can be used to
+    /// slice the original source as is.
+    fn new(src: &'src str) -> Cursor<'src> {
+        let source = src.strip_prefix(BOM).unwrap_or(src);
+        Cursor {
+            source,
+            orig: src,
+            prev: None,
+            curr: None,
+            position: src.len() - source.len(),
+        }
+    }
+
+    fn at(src: &'src str, position: usize) -> Cursor<'src> {
         Cursor {
-            source: src,
+            source: &src[position..],
             orig: src,
             prev: None,
             curr: None,
-            position: 0,
+            position,
         }
     }
 
@@ -288,10 +636,27 @@ impl<'src> Cursor<'src> {
 
     fn bump(&mut self) {
         if self.source.is_empty().not() {
-            self.prev = self.curr;
-            self.curr = self.source.chars().next();
-            self.source = &self.source[1..];
-            self.position += 1;
+            self.next();
+        }
+    }
+
+    /// Bumps every leading ASCII byte of the source matching `predicate` in
+    /// one go, without decoding chars.
+    fn bump_ascii_while(&mut self, predicate: impl Fn(u8) -> bool) {
+        let bytes = self.source.as_bytes();
+        let n = bytes
+            .iter()
+            .position(|&b| !(b.is_ascii() && predicate(b)))
+            .unwrap_or(bytes.len());
+
+        if n > 0 {
+            self.prev = match n {
+                1 => self.curr,
+                _ => Some(bytes[n - 2] as char),
+            };
+            self.curr = Some(bytes[n - 1] as char);
+            self.source = &self.source[n..];
+            self.position += n;
         }
     }
 
@@ -300,11 +665,491 @@ impl<'src> Cursor<'src> {
         match self.source.chars().next() {
             Some(c) => {
                 self.curr = Some(c);
-                self.source = &self.source[1..];
-                self.position += 1;
+                self.source = &self.source[c.len_utf8()..];
+                self.position += c.len_utf8();
                 Some(c)
             }
             None => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::{ErrorKind, NumberErrorReason, Scanner, TokenKind};
+    use crate::span::{Location, Span};
+
+    fn number_error(source: &str) -> (Span, ErrorKind) {
+        let err = Scanner::new(source)
+            .find_map(Result::err)
+            .expect("The source contains an invalid number");
+        (err.span, err.kind)
+    }
+
+    #[test]
+    fn number_with_exponent() {
+        let tokens: Vec<_> = Scanner::new("1e10 2.5E-3")
+            .map(|t| t.expect("Both numbers are valid").tipo)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::Number,
+                TokenKind::Whitespace,
+                TokenKind::Number,
+                TokenKind::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn number_multiple_decimal_points() {
+        let source = "1.2.3 + 1";
+        let (span, kind) = number_error(source);
+
+        assert_eq!(&source[span.range()], "1.2.3");
+        assert_eq!(
+            kind,
+            ErrorKind::InvalidNumber {
+                reason: NumberErrorReason::MultipleDecimalPoints
+            }
+        );
+    }
+
+    #[test]
+    fn number_missing_exponent_digits() {
+        let source = "1e + 1";
+        let (span, kind) = number_error(source);
+
+        assert_eq!(&source[span.range()], "1e");
+        assert_eq!(
+            kind,
+            ErrorKind::InvalidNumber {
+                reason: NumberErrorReason::MissingExponentDigits
+            }
+        );
+        assert_eq!(
+            kind.to_string(),
+            "invalid number, the exponent has no digits"
+        );
+    }
+
+    #[test]
+    fn number_error_covers_whole_literal() {
+        let source = "1.2.3a4;";
+        let (span, _) = number_error(source);
+
+        assert_eq!(&source[span.range()], "1.2.3a4");
+    }
+
+    #[test]
+    fn leading_bom_is_skipped() {
+        let source = "\u{FEFF}print 1;";
+        let tokens: Vec<_> = Scanner::new(source)
+            .map(|t| t.expect("The BOM is not a token"))
+            .collect();
+
+        assert_eq!(
+            tokens.iter().map(|t| t.tipo).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Print,
+                TokenKind::Whitespace,
+                TokenKind::Number,
+                TokenKind::Semicolon,
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(&source[tokens[0].span.range()], "print");
+        assert_eq!(
+            tokens[0].span.get_start_location(source),
+            Location { line: 1, col: 1 }
+        );
+    }
+
+    #[test]
+    fn bom_after_start_is_unknown() {
+        let source = "print \u{FEFF}1;";
+        let err = Scanner::new(source)
+            .find_map(Result::err)
+            .expect("A BOM past offset 0 is an error");
+
+        assert_eq!(err.kind, ErrorKind::UnknownToken);
+        assert_eq!(&source[err.span.range()], "\u{FEFF}");
+    }
+
+    #[test]
+    fn doc_comments() {
+        let source = "/// hi\n//// hi\n// / hi";
+        let (tokens, errors) = Scanner::new(source).scan_all();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|t| (t.tipo, &source[t.span.range()]))
+                .collect::<Vec<_>>(),
+            vec![
+                (TokenKind::DocComment, "/// hi"),
+                (TokenKind::Whitespace, "\n"),
+                (TokenKind::DocComment, "//// hi"),
+                (TokenKind::Whitespace, "\n"),
+                (TokenKind::CommentLine, "// / hi"),
+                (TokenKind::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let source = "a & b | c ^ d << e >> f and g or h <= i >= j";
+        let tokens: Vec<_> = Scanner::new(source)
+            .map(|t| t.expect("All tokens are valid").tipo)
+            .filter(|t| *t != TokenKind::Whitespace && *t != TokenKind::Identifier)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::Ampersand,
+                TokenKind::Pipe,
+                TokenKind::Caret,
+                TokenKind::LessLess,
+                TokenKind::GreaterGreater,
+                TokenKind::And,
+                TokenKind::Or,
+                TokenKind::LessEqual,
+                TokenKind::GreaterEqual,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn compound_assignment_operators() {
+        let source = "a += b -= c *= d /= e %= f % g ** h // i";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("All tokens are valid").tipo)
+            .filter(|t| *t != TokenKind::Identifier)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::PlusEqual,
+                TokenKind::MinusEqual,
+                TokenKind::StarEqual,
+                TokenKind::SlashEqual,
+                TokenKind::PercentEqual,
+                TokenKind::Percent,
+                TokenKind::StarStar,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn ends_with_a_single_eof() {
+        for source in ["", "print 1;", "1 // done\n"] {
+            let mut scanner = Scanner::new(source);
+            let last = scanner
+                .by_ref()
+                .last()
+                .expect("There is always a token")
+                .expect("All tokens are valid");
+
+            assert_eq!(last.tipo, TokenKind::Eof);
+            assert_eq!(last.span, Span::from(source.len()..source.len()));
+            assert!(scanner.next().is_none());
+        }
+
+        let eofs = Scanner::new("a b")
+            .filter(|t| t.as_ref().is_ok_and(|t| t.tipo == TokenKind::Eof))
+            .count();
+        assert_eq!(eofs, 1);
+    }
+
+    #[test]
+    fn token_kind_display() {
+        assert_eq!(TokenKind::Number.to_string(), "number");
+        assert_eq!(TokenKind::Identifier.to_string(), "identifier");
+        assert_eq!(TokenKind::LeftParen.to_string(), "'('");
+        assert_eq!(TokenKind::EqualEqual.to_string(), "'=='");
+        assert_eq!(TokenKind::Nil.to_string(), "'nil'");
+        assert_eq!(TokenKind::Eof.to_string(), "end of file");
+    }
+
+    #[test]
+    fn resume_after_append() {
+        let mut buffer = String::from("1 +");
+        let mut tokens = Scanner::new(&buffer).scan_all().0;
+        tokens
+            .pop()
+            .filter(|t| t.tipo == TokenKind::Eof)
+            .expect("The source ends");
+        let last = tokens.pop().expect("There are tokens to resume from");
+
+        buffer.push_str(" 2");
+        tokens.extend(Scanner::resume_at(&buffer, last.span.start).scan_all().0);
+
+        assert_eq!(tokens, Scanner::new("1 + 2").scan_all().0);
+    }
+
+    #[test]
+    fn resume_after_multibyte_characters() {
+        let source = "var ñandú = \"é\";";
+        let tokens = Scanner::new(source).scan_all().0;
+        let equal = source.find('=').expect("There is an `=`");
+        let from = tokens
+            .iter()
+            .position(|t| t.span.start == equal)
+            .expect("`=` is a token");
+
+        let resumed = Scanner::resume_at(source, equal).scan_all().0;
+        assert_eq!(resumed, tokens[from..]);
+
+        // Halfway through `é`, which starts at byte 15
+        let scanner = Scanner::resume_at(source, 16);
+        assert_eq!(scanner.position(), 15);
+        let (_, errors) = scanner.scan_all();
+        assert_eq!(errors[0].span.start, 15);
+
+        let scanner = Scanner::resume_at(source, source.len() + 1);
+        assert_eq!(scanner.position(), source.len());
+    }
+
+    #[test]
+    fn resume_continues_split_token() {
+        let mut buffer = String::from("12");
+        let scanner = Scanner::new(&buffer);
+        assert_eq!(scanner.position(), 0);
+        let mut tokens = scanner.scan_all().0;
+        tokens
+            .pop()
+            .filter(|t| t.tipo == TokenKind::Eof)
+            .expect("The source ends");
+        let last = tokens.pop().expect("There are tokens to resume from");
+
+        buffer.push_str("34");
+        tokens.extend(Scanner::resume_at(&buffer, last.span.start).scan_all().0);
+
+        assert_eq!(tokens, Scanner::new("1234").scan_all().0);
+        assert_eq!(tokens[0].span, Span::from(0..4));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn tokens_json_round_trip() {
+        let source = "print 1.5;";
+        let (tokens, _) = Scanner::new(source).scan_all();
+        let json = super::tokens_to_json(&tokens, source);
+
+        assert!(
+            json.starts_with(r#"[{"kind":"Print","span":{"start":0,"end":5},"lexeme":"print"},"#)
+        );
+
+        let parsed: Vec<super::Token> =
+            serde_json::from_str(&json).expect("The JSON holds valid tokens");
+        assert_eq!(parsed, tokens);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn error_json_round_trip() {
+        let err = Scanner::new("1.2.3")
+            .find_map(Result::err)
+            .expect("The number is invalid");
+        let json = serde_json::to_string(&err).expect("Errors are serializable");
+
+        assert_eq!(
+            json,
+            r#"{"span":{"start":0,"end":5},"kind":{"InvalidNumber":{"reason":"MultipleDecimalPoints"}}}"#
+        );
+
+        let parsed: super::Error = serde_json::from_str(&json).expect("The JSON is an error");
+        assert_eq!(parsed.span, err.span);
+        assert_eq!(parsed.kind, err.kind);
+    }
+
+    #[test]
+    fn keyword_boundaries() {
+        let source = "for forx or orchid super superb fun funny this thistle _if \
+                      break breaks class continue cont";
+        let tokens: Vec<_> = Scanner::new(source)
+            .map(|t| t.expect("All tokens are valid").tipo)
+            .filter(|t| *t != TokenKind::Whitespace)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::For,
+                TokenKind::Identifier,
+                TokenKind::Or,
+                TokenKind::Identifier,
+                TokenKind::Super,
+                TokenKind::Identifier,
+                TokenKind::Fun,
+                TokenKind::Identifier,
+                TokenKind::This,
+                TokenKind::Identifier,
+                TokenKind::Identifier,
+                TokenKind::Break,
+                TokenKind::Identifier,
+                TokenKind::Class,
+                TokenKind::Continue,
+                TokenKind::Identifier,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn string_value_borrows() {
+        let source = "print \"hi there\"; \"\";";
+        let strings: Vec<_> = Scanner::new(source)
+            .map(|t| t.expect("All tokens are valid"))
+            .filter(|t| t.tipo == TokenKind::String)
+            .collect();
+
+        assert_eq!(strings[0].lexeme(source), "\"hi there\"");
+        let value = strings[0].string_value(source);
+        assert_eq!(value, "hi there");
+        assert!(matches!(value, Cow::Borrowed(_)));
+        assert_eq!(strings[1].string_value(source), "");
+    }
+
+    /// How long it takes is measured by `benches/scanner.rs`.
+    #[test]
+    fn reads_many_string_literals() {
+        let source: String = (0..100_000).map(|i| format!("\"string {i}\" ")).collect();
+
+        let bytes: usize = Scanner::new(&source)
+            .map(|t| t.expect("All tokens are valid"))
+            .filter(|t| t.tipo == TokenKind::String)
+            .map(|t| t.string_value(&source).len())
+            .sum();
+
+        assert_eq!(bytes, source.len() - 3 * 100_000);
+    }
+
+    /// How long it takes is measured by `benches/scanner.rs`.
+    #[test]
+    fn lexes_many_identifiers() {
+        let source: String = (0..100_000).map(|i| format!("ident_{i} ")).collect();
+
+        let identifiers = Scanner::new(&source)
+            .map(|t| t.expect("All tokens are valid"))
+            .filter(|t| t.tipo == TokenKind::Identifier)
+            .count();
+
+        assert_eq!(identifiers, 100_000);
+    }
+
+    #[test]
+    fn significant_skips_trivia() {
+        let source = "/// doc\nprint 1; // done\n";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("All tokens are valid").tipo)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::Print,
+                TokenKind::Number,
+                TokenKind::Semicolon,
+                TokenKind::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn significant_on_trivia_only_source() {
+        let significant = |source| {
+            Scanner::new(source)
+                .significant()
+                .map(|t| t.expect("All tokens are valid").tipo)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(significant(""), vec![TokenKind::Eof]);
+        assert_eq!(significant(" \n\t// nothing\n"), vec![TokenKind::Eof]);
+    }
+
+    #[test]
+    fn significant_keeps_errors() {
+        let errors: Vec<_> = Scanner::new(" @ ")
+            .significant()
+            .filter_map(|t| t.err().map(|err| err.kind))
+            .collect();
+
+        assert_eq!(errors, vec![ErrorKind::UnknownToken]);
+    }
+
+    #[test]
+    fn tokenize_keeps_good_tokens() {
+        let (tokens, errors) = Scanner::new("var a = @ 1; // done").tokenize();
+
+        assert_eq!(
+            tokens.iter().map(|t| t.tipo).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Var,
+                TokenKind::Identifier,
+                TokenKind::Equal,
+                TokenKind::Number,
+                TokenKind::Semicolon,
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ErrorKind::UnknownToken);
+        assert_eq!(errors[0].span, Span::from(8..9));
+    }
+
+    #[test]
+    fn token_categories() {
+        let categories = |tipo: TokenKind| {
+            [
+                tipo.is_trivia(),
+                tipo.is_keyword(),
+                tipo.is_binary_operator(),
+                tipo.is_literal_start(),
+            ]
+        };
+
+        assert_eq!(
+            categories(TokenKind::CommentLine),
+            [true, false, false, false]
+        );
+        assert_eq!(categories(TokenKind::While), [false, true, false, false]);
+        assert_eq!(categories(TokenKind::Plus), [false, false, true, false]);
+        assert_eq!(categories(TokenKind::Number), [false, false, false, true]);
+        // Some tokens belong to two groups, or to none
+        assert_eq!(categories(TokenKind::Or), [false, true, true, false]);
+        assert_eq!(categories(TokenKind::Nil), [false, true, false, true]);
+        assert_eq!(categories(TokenKind::Identifier), [false; 4]);
+        assert_eq!(categories(TokenKind::Bang), [false; 4]);
+    }
+
+    #[test]
+    fn keywords_scan_as_keywords() {
+        let source = "and break class continue else false for fun if nil or print \
+                      return super this true var while";
+        let tokens: Vec<_> = Scanner::new(source)
+            .significant()
+            .map(|t| t.expect("It's guaranteed to be valid").tipo)
+            .filter(|t| *t != TokenKind::Eof)
+            .collect();
+        assert_eq!(tokens.len(), 18);
+
+        for (tipo, word) in tokens.into_iter().zip(source.split_whitespace()) {
+            assert!(tipo.is_keyword(), "{word} is a keyword");
+            assert_eq!(tipo.to_string(), format!("'{word}'"));
+        }
+    }
+}
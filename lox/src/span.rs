@@ -1,12 +1,18 @@
 use std::ops::Range;
 
+/// The byte order mark some editors write at the start of a file. It isn't
+/// part of the source: the scanner skips it and locations don't count it.
+pub const BOM: char = '\u{FEFF}';
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: usize,
     pub end: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Location {
     pub line: usize,
     pub col: usize,
@@ -28,8 +34,18 @@ impl Default for Span {
 }
 
 impl Span {
+    /// The smallest span enclosing both `self` and `rhs`, in any order.
     pub fn join(&self, rhs: Span) -> Span {
-        Span::from(self.start..rhs.end)
+        Span::merge_all([*self, rhs]).expect("There are two spans to merge")
+    }
+
+    /// The smallest span enclosing every span of `spans`, in any order, or
+    /// `None` when there are none.
+    pub fn merge_all(spans: impl IntoIterator<Item = Span>) -> Option<Span> {
+        spans.into_iter().reduce(|acc, span| Span {
+            start: acc.start.min(span.start),
+            end: acc.end.max(span.end),
+        })
     }
 
     pub fn range(&self) -> Range<usize> {
@@ -40,25 +56,55 @@ impl Span {
         self.range().len()
     }
 
+    /// A zero-width span, such as the insertion point of a missing `;`.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether `other` lies entirely within `self`, both taken as half-open
+    /// `[start, end)` ranges. Every span contains itself.
+    pub fn contains(&self, other: Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Whether `self` and `other` share at least one byte. Spans that only
+    /// touch, like `0..2` and `2..4`, don't overlap, and neither does an
+    /// empty span.
+    pub fn overlaps(&self, other: Span) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+
     pub fn get_start_location(&self, source: &str) -> Location {
         Self::get_location(source, self.start)
     }
 
+    /// The location of the last character of the span, which may be several
+    /// bytes long. An empty span ends where it starts.
     pub fn get_end_location(&self, source: &str) -> Location {
-        Self::get_location(source, self.end - 1)
+        if self.is_empty() {
+            return self.get_start_location(source);
+        }
+
+        let last = source[..self.end]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i);
+        Self::get_location(source, last)
     }
 
+    /// The 1-based line and column of the byte at `index`, the column
+    /// counting the characters since the last newline.
     pub fn get_location(source: &str, index: usize) -> Location {
-        let line = source[..index].chars().filter(|a| a == &'\n').count();
-        let col = source[..index]
-            .chars()
-            .rev()
-            .position(|c| c == '\n')
-            .unwrap_or(index);
+        let prefix = &source[..index];
+        let (line, line_start) = match prefix.rfind('\n') {
+            Some(newline) => (prefix.matches('\n').count(), &prefix[newline + 1..]),
+            // The scanner skips a leading byte order mark, so it takes no column
+            None => (0, prefix.strip_prefix(BOM).unwrap_or(prefix)),
+        };
 
         Location {
             line: line + 1,
-            col: col + 1,
+            col: line_start.chars().count() + 1,
         }
     }
 }
@@ -106,4 +152,113 @@ mod test {
         assert_eq!(line, 8);
         assert_eq!(col, 3);
     }
+
+    #[test]
+    fn zero_width() {
+        let source = "print 1\nprint 2";
+        let span = Span::from(7..7);
+
+        assert!(span.is_empty());
+        assert_eq!(span.len(), 0);
+        assert_eq!(
+            span.get_start_location(source),
+            Location { line: 1, col: 8 }
+        );
+        assert_eq!(span.get_end_location(source), Location { line: 1, col: 8 });
+
+        let span = Span::from(0..0);
+        assert_eq!(
+            span.get_start_location(source),
+            Location { line: 1, col: 1 }
+        );
+        assert_eq!(span.get_end_location(source), Location { line: 1, col: 1 });
+
+        let end = source.len();
+        let span = Span::from(end..end);
+        assert_eq!(span.get_end_location(source), Location { line: 2, col: 8 });
+        assert!(!Span::from(0..1).is_empty());
+    }
+
+    #[test]
+    fn contains() {
+        let span = Span::from(2..6);
+
+        assert!(span.contains(span));
+        assert!(span.contains(Span::from(3..5)));
+        assert!(span.contains(Span::from(2..3)));
+        assert!(span.contains(Span::from(5..6)));
+        assert!(span.contains(Span::from(4..4)));
+
+        assert!(!span.contains(Span::from(1..3)));
+        assert!(!span.contains(Span::from(5..7)));
+        assert!(!span.contains(Span::from(0..8)));
+        assert!(!span.contains(Span::from(6..8)));
+        assert!(!Span::from(3..5).contains(span));
+    }
+
+    #[test]
+    fn overlaps() {
+        let span = Span::from(2..6);
+
+        assert!(span.overlaps(span));
+        assert!(span.overlaps(Span::from(3..5)));
+        assert!(Span::from(3..5).overlaps(span));
+        assert!(span.overlaps(Span::from(0..3)));
+        assert!(span.overlaps(Span::from(5..9)));
+
+        // Touching
+        assert!(!span.overlaps(Span::from(0..2)));
+        assert!(!span.overlaps(Span::from(6..8)));
+        // Disjoint
+        assert!(!span.overlaps(Span::from(8..9)));
+        assert!(!Span::from(8..9).overlaps(span));
+        // Empty
+        assert!(!span.overlaps(Span::from(4..4)));
+        assert!(!Span::from(4..4).overlaps(span));
+    }
+
+    #[test]
+    fn merge_all() {
+        assert_eq!(Span::merge_all([]), None);
+        assert_eq!(Span::merge_all([Span::from(3..5)]), Some(Span::from(3..5)));
+        assert_eq!(
+            Span::merge_all([Span::from(0..2), Span::from(4..6), Span::from(8..9)]),
+            Some(Span::from(0..9))
+        );
+        // Reversed
+        assert_eq!(
+            Span::merge_all([Span::from(8..9), Span::from(4..6), Span::from(0..2)]),
+            Some(Span::from(0..9))
+        );
+        // Overlapping and nested
+        assert_eq!(
+            Span::merge_all([Span::from(2..7), Span::from(0..4), Span::from(3..5)]),
+            Some(Span::from(0..7))
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(Span::from(0..2).join(Span::from(4..6)), Span::from(0..6));
+        assert_eq!(Span::from(4..6).join(Span::from(0..2)), Span::from(0..6));
+        assert_eq!(Span::from(0..6).join(Span::from(2..3)), Span::from(0..6));
+    }
+
+    #[test]
+    fn columns() {
+        let source = "ab\ncd\n\nαβ x";
+
+        // Column 1, on the first and on later lines
+        assert_eq!(Span::get_location(source, 0), Location { line: 1, col: 1 });
+        assert_eq!(Span::get_location(source, 3), Location { line: 2, col: 1 });
+        // Right after a newline, even when the line is empty
+        assert_eq!(Span::get_location(source, 6), Location { line: 3, col: 1 });
+        assert_eq!(Span::get_location(source, 7), Location { line: 4, col: 1 });
+        // Within the first line
+        assert_eq!(Span::get_location(source, 1), Location { line: 1, col: 2 });
+        // The newline itself ends its line
+        assert_eq!(Span::get_location(source, 2), Location { line: 1, col: 3 });
+        // Characters, not bytes
+        assert_eq!(Span::get_location(source, 12), Location { line: 4, col: 4 });
+    }
 }
@@ -0,0 +1,277 @@
+use crate::{
+    ast::{
+        BinaryKind, Expression, ExpressionItem, Function, Identifier, LogicalKind, Statement,
+        UnaryKind,
+    },
+    span::Span,
+};
+
+/// Traverses the [`Expression`] and [`Statement`] trees.
+///
+/// Every method defaults to visiting the children, so an implementor only
+/// overrides the nodes it cares about. Overriding [`Visitor::visit_expression`]
+/// or [`Visitor::visit_statement`] sees every node, call [`walk_expression`]
+/// or [`walk_statement`] from them to keep recursing.
+pub trait Visitor: Sized {
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_binary(&mut self, lhs: &Expression, rhs: &Expression, _kind: &BinaryKind) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_logical(&mut self, lhs: &Expression, rhs: &Expression, _kind: &LogicalKind) {
+        self.visit_expression(lhs);
+        self.visit_expression(rhs);
+    }
+
+    fn visit_unary(&mut self, expr: &Expression, _kind: &UnaryKind) {
+        self.visit_expression(expr);
+    }
+
+    fn visit_grouping(&mut self, expr: &Expression) {
+        self.visit_expression(expr);
+    }
+
+    /// Called for `Number`, `String`, `Bool` and `Nil` expressions.
+    fn visit_literal(&mut self, _literal: &Expression) {}
+
+    fn visit_variable(&mut self, _variable: &Expression) {}
+
+    fn visit_comma(&mut self, exprs: &[Expression]) {
+        for expr in exprs {
+            self.visit_expression(expr);
+        }
+    }
+
+    fn visit_this(&mut self, _this: &Expression) {}
+
+    fn visit_super(&mut self, _method: &Identifier) {}
+
+    fn visit_assign(&mut self, _name: &Identifier, value: &Expression) {
+        self.visit_expression(value);
+    }
+
+    fn visit_call(&mut self, callee: &Expression, args: &[Expression]) {
+        self.visit_expression(callee);
+        for arg in args {
+            self.visit_expression(arg);
+        }
+    }
+
+    fn visit_get(&mut self, object: &Expression, _name: &Identifier) {
+        self.visit_expression(object);
+    }
+
+    fn visit_set(&mut self, object: &Expression, _name: &Identifier, value: &Expression) {
+        self.visit_expression(object);
+        self.visit_expression(value);
+    }
+
+    fn visit_lambda(&mut self, _params: &[Identifier], body: &[Statement]) {
+        for statement in body {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_expression_statement(&mut self, expr: &Expression) {
+        self.visit_expression(expr);
+    }
+
+    fn visit_print(&mut self, values: &[Expression]) {
+        for value in values {
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_var(&mut self, _name: &Identifier, initializer: Option<&Expression>) {
+        if let Some(initializer) = initializer {
+            self.visit_expression(initializer);
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.visit_statement(statement);
+        }
+    }
+
+    /// Both function declarations and the methods of a class.
+    fn visit_function(&mut self, function: &Function) {
+        for statement in &function.body {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_class(
+        &mut self,
+        _name: &Identifier,
+        _superclass: Option<&Identifier>,
+        methods: &[Function],
+    ) {
+        for method in methods {
+            self.visit_function(method);
+        }
+    }
+
+    fn visit_return(&mut self, _keyword_span: Span, value: Option<&Expression>) {
+        if let Some(value) = value {
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_break(&mut self, _keyword_span: Span) {}
+
+    fn visit_continue(&mut self, _keyword_span: Span) {}
+
+    fn visit_while(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+        increment: Option<&Expression>,
+    ) {
+        self.visit_expression(condition);
+        self.visit_statement(body);
+        if let Some(increment) = increment {
+            self.visit_expression(increment);
+        }
+    }
+}
+
+/// Dispatches `expr` to the matching [`Visitor`] method.
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expr: &Expression) {
+    match &expr.item {
+        ExpressionItem::Binary(lhs, rhs, kind, _) => visitor.visit_binary(lhs, rhs, kind),
+        ExpressionItem::Logical(lhs, rhs, kind) => visitor.visit_logical(lhs, rhs, kind),
+        ExpressionItem::Unary(expr, kind) => visitor.visit_unary(expr, kind),
+        ExpressionItem::Grouping(expr) => visitor.visit_grouping(expr),
+        ExpressionItem::Number(_)
+        | ExpressionItem::String(_)
+        | ExpressionItem::Bool(_)
+        | ExpressionItem::Nil => visitor.visit_literal(expr),
+        ExpressionItem::Variable(_) => visitor.visit_variable(expr),
+        ExpressionItem::Assign { name, value } => visitor.visit_assign(name, value),
+        ExpressionItem::Call { callee, args, .. } => visitor.visit_call(callee, args),
+        ExpressionItem::Get { object, name } => visitor.visit_get(object, name),
+        ExpressionItem::Comma(exprs) => visitor.visit_comma(exprs),
+        ExpressionItem::This => visitor.visit_this(expr),
+        ExpressionItem::Super { method, .. } => visitor.visit_super(method),
+        ExpressionItem::Set {
+            object,
+            name,
+            value,
+        } => visitor.visit_set(object, name, value),
+        ExpressionItem::Lambda { params, body } => visitor.visit_lambda(params, body),
+    }
+}
+
+/// Dispatches `statement` to the matching [`Visitor`] method.
+pub fn walk_statement<V: Visitor>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Expression(expr) => visitor.visit_expression_statement(expr),
+        Statement::Print(values) => visitor.visit_print(values),
+        Statement::Var { name, initializer } => visitor.visit_var(name, initializer.as_ref()),
+        Statement::Block(statements) => visitor.visit_block(statements),
+        Statement::Function(function) => visitor.visit_function(function),
+        Statement::Class {
+            name,
+            superclass,
+            methods,
+        } => visitor.visit_class(name, superclass.as_ref(), methods),
+        Statement::Return {
+            keyword_span,
+            value,
+        } => visitor.visit_return(*keyword_span, value.as_ref()),
+        Statement::Break(keyword_span) => visitor.visit_break(*keyword_span),
+        Statement::Continue(keyword_span) => visitor.visit_continue(*keyword_span),
+        Statement::While {
+            condition,
+            body,
+            increment,
+        } => visitor.visit_while(condition, body, increment.as_ref()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{walk_expression, walk_statement, Visitor};
+    use crate::{
+        ast::{Expression, Statement},
+        parser::Parser,
+        scanner::Scanner,
+    };
+
+    /// Counts every node, and the variables among them.
+    #[derive(Default)]
+    struct Counter {
+        expressions: usize,
+        statements: usize,
+        variables: Vec<String>,
+    }
+
+    impl Visitor for Counter {
+        fn visit_expression(&mut self, expr: &Expression) {
+            self.expressions += 1;
+            walk_expression(self, expr);
+        }
+
+        fn visit_statement(&mut self, statement: &Statement) {
+            self.statements += 1;
+            walk_statement(self, statement);
+        }
+
+        fn visit_variable(&mut self, variable: &Expression) {
+            self.variables.push(variable.to_string());
+        }
+    }
+
+    fn count(source: &str) -> Counter {
+        let (tokens, _) = Scanner::new(source).tokenize();
+        let program = Parser::new(&tokens, source)
+            .parse_program()
+            .expect("It's guaranteed to parse");
+
+        let mut counter = Counter::default();
+        for statement in &program.statements {
+            counter.visit_statement(statement);
+        }
+        counter
+    }
+
+    #[test]
+    fn reaches_both_sides_of_operators() {
+        let counter = count("-a + !(b * c);");
+        assert_eq!(counter.statements, 1);
+        // The sum, `-a`, `a`, `!(..)`, the group, the product, `b` and `c`
+        assert_eq!(counter.expressions, 8);
+        assert_eq!(counter.variables, ["a", "b", "c"]);
+
+        let counter = count("a and (b or c), -(-d);");
+        assert_eq!(counter.variables, ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn reaches_nested_statements() {
+        let source = "
+            var x = a;
+            { print b, c; }
+            for (var i = 0; i < d; i = i + e) if_ = f(g.h, i.j = k);
+            fun m(p) { return n; }
+            class C < D { o() { var l = fun () { q; }; } }
+        ";
+        let counter = count(source);
+        assert_eq!(
+            counter.variables,
+            ["a", "b", "c", "i", "d", "f", "g", "i", "k", "i", "e", "n", "q"]
+        );
+        // The top-level ones, the `print`, the desugared `for` with its
+        // initializer, loop and body, the `return`, the `var` and `q;`
+        assert_eq!(counter.statements, 5 + 1 + 3 + 1 + 1 + 1);
+    }
+}
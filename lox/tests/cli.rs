@@ -0,0 +1,457 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Writes `source` to a file named after the test and runs `lox` on it
+/// with `args` in front of the path.
+fn lox_file(name: &str, source: &str, args: &[&str]) -> Output {
+    let path: PathBuf = std::env::temp_dir().join(format!("lox-cli-{name}.lox"));
+    std::fs::write(&path, source).expect("The temporary dir is writable");
+
+    Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .expect("The lox binary runs")
+}
+
+/// Runs `lox` with `args`, writing `source` to its stdin.
+fn lox_stdin(source: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("The lox binary runs");
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(source.as_bytes())
+        .expect("The child reads its stdin");
+
+    child.wait_with_output().expect("The lox binary runs")
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).expect("The output is valid UTF-8")
+}
+
+#[test]
+fn ast_flag_prints_s_expression() {
+    let output = lox_file("ast", "1 + 2 * -3;\n!true;", &["--ast"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "(+ 1 (* 2 (- 3)))\n(! true)\n");
+}
+
+#[test]
+fn inline_expression_is_evaluated() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["-e", "1 + 2 * 3"])
+        .output()
+        .expect("The lox binary runs");
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "7\n");
+}
+
+#[test]
+fn inline_expression_warns() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["--color=never", "-e", "var x; while (x = nil) {}"])
+        .output()
+        .expect("The lox binary runs");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Warning at <-e>:1:17: assignment used as a condition\n"));
+    assert!(stderr.contains("------- help: use `==` to compare, or wrap in parentheses"));
+}
+
+#[test]
+fn inline_expression_runtime_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["-e", "1 + nil"])
+        .output()
+        .expect("The lox binary runs");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<-e>:1:3"));
+}
+
+#[test]
+fn dash_reads_program_from_stdin() {
+    let output = lox_stdin("1 + 2;", &["--ast", "-"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "(+ 1 2)\n");
+}
+
+#[test]
+fn stdin_errors_point_at_stdin() {
+    let output = lox_stdin("(1 + 2", &["-"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<stdin>:1:"));
+}
+
+#[test]
+fn missing_semicolon_is_reported() {
+    let output = lox_file("semicolon", "1 + 2", &["--ast"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(":1:6"));
+    assert!(stderr.contains("expected `;` after expression"));
+    assert!(stderr.contains("   1 | 1 + 2\n             ^"));
+}
+
+#[test]
+fn missing_semicolon_points_after_the_line() {
+    let output = lox_file(
+        "semicolon-mid-file",
+        "var a = 1\nprint a;",
+        &["--color=never"],
+    );
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(":1:10: Parser error: expected `;` after expression"));
+    assert!(stderr.contains("   1 | var a = 1\n                 ^"));
+}
+
+#[test]
+fn repl_continues_unfinished_input() {
+    let output = lox_stdin("(1 +\n2)\n", &[]);
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(stdout(&output), "> ... 3\n> ");
+}
+
+#[test]
+fn repl_tokens_command() {
+    let output = lox_stdin(":tokens\n1 + 2\n1 + 2\n", &["--color=never"]);
+
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    let (tokens, value) = stdout
+        .split_once("> 3\n")
+        .expect("The second line prints its value");
+    assert!(tokens.contains("  Number         0..1       \"1\""));
+    assert!(tokens.contains("  Plus           2..3       \"+\""));
+    assert_eq!(value, "> ");
+}
+
+#[test]
+fn repl_commands() {
+    let output = lox_stdin(
+        "1 + 2\n:ast\n1 + 2\nvar a;\n:ast\n-1\n:nope\n:quit\n3\n",
+        &[],
+    );
+
+    assert!(output.status.success());
+    let (values, help) = stdout(&output)
+        .split_once(":ast ")
+        .expect("The unknown command prints the help");
+    assert_eq!(values, "> 3\n> > (+ 1 2)\n> (var a)\n> > -1\n> ");
+    // Nothing runs after `:quit`
+    assert!(help.ends_with(":quit    exit the REPL\n> "));
+}
+
+#[test]
+fn repl_falls_back_to_statements() {
+    let output = lox_stdin("1 + 2\nvar x = 1; print x + 1;\n", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "> 3\n> 2\n> ");
+}
+
+#[test]
+fn repl_keeps_variables_between_lines() {
+    let output = lox_stdin("var x = 1;\nx = x + 1\nprint x;\n", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "> > 2\n> 2\n> ");
+}
+
+#[test]
+fn print_statements_run() {
+    let output = lox_file("print", "print 1 + 2;\nprint \"hi\";\n1;", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\nhi\n");
+}
+
+#[test]
+fn print_many_values() {
+    let output = lox_file("print-many", "print 1, \"x\", nil;\nprint (1, 2);", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1 x nil\n2\n");
+}
+
+#[test]
+fn dash_runs_program_from_stdin() {
+    let output = lox_stdin("print 1;", &["-"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+fn no_color_disables_escapes() {
+    let path = std::env::temp_dir().join("lox-cli-no-color.lox");
+    std::fs::write(&path, "1 +").expect("The temporary dir is writable");
+
+    let run = |no_color: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lox"));
+        command.arg(&path).env("FORCE_COLOR", "1");
+        if no_color {
+            command.env("NO_COLOR", "1");
+        } else {
+            command.env_remove("NO_COLOR");
+        }
+        command.output().expect("The lox binary runs")
+    };
+
+    assert!(run(false).stderr.contains(&b'\x1b'));
+    assert!(!run(true).stderr.contains(&b'\x1b'));
+}
+
+#[test]
+fn color_flag_overrides_detection() {
+    let always = lox_file("color-always", "1 +", &["--color=always"]);
+    assert!(always.stderr.contains(&b'\x1b'));
+
+    let never = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["--color=never", "-e", "1 +"])
+        .env("FORCE_COLOR", "1")
+        .output()
+        .expect("The lox binary runs");
+    assert!(!never.stderr.is_empty());
+    assert!(!never.stderr.contains(&b'\x1b'));
+
+    let unknown = lox_file("color-unknown", "1;", &["--color=sometimes"]);
+    assert!(!unknown.status.success());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn diagnostics_json() {
+    let output = lox_file(
+        "diagnostics-json",
+        "print 1;\nprint (2;",
+        &["--emit=diagnostics-json"],
+    );
+
+    assert!(!output.status.success());
+    let path = std::env::temp_dir().join("lox-cli-diagnostics-json.lox");
+    let diagnostics: serde_json::Value =
+        serde_json::from_str(stdout(&output)).expect("The output is JSON");
+    assert_eq!(
+        diagnostics,
+        serde_json::json!([{
+            "file": path.to_string_lossy(),
+            "start": {"line": 2, "col": 9},
+            "end": {"line": 2, "col": 9},
+            "message": "Parser error: expected ')' to close '(', found ';'",
+            "severity": "error",
+        }])
+    );
+}
+
+/// The program isn't run, what it prints would break the JSON
+#[cfg(feature = "serde")]
+#[test]
+fn diagnostics_json_does_not_run_the_program() {
+    let output = lox_file(
+        "diagnostics-json-print",
+        "print 1;\nvar x; while (x = nil) {}\n1 + \"x\";",
+        &["--emit=diagnostics-json"],
+    );
+
+    assert!(output.status.success());
+    let diagnostics: serde_json::Value =
+        serde_json::from_str(stdout(&output)).expect("The output is JSON");
+    let diagnostics = diagnostics.as_array().expect("The output is an array");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], "warning");
+}
+
+/// Pins the schema, editors rely on it
+#[cfg(feature = "serde")]
+#[test]
+fn ast_json_golden() {
+    let output = lox_file("ast-json", "1 + \"x\";", &["--ast=json"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        r#"{
+  "statements": [
+    {
+      "kind": "Expression",
+      "value": {
+        "span": {
+          "start": 0,
+          "end": 7
+        },
+        "kind": "Binary",
+        "value": [
+          {
+            "span": {
+              "start": 0,
+              "end": 1
+            },
+            "kind": "Number",
+            "value": 1.0
+          },
+          {
+            "span": {
+              "start": 4,
+              "end": 7
+            },
+            "kind": "String",
+            "value": "x"
+          },
+          "Plus",
+          {
+            "start": 2,
+            "end": 3
+          }
+        ]
+      }
+    }
+  ]
+}
+"#
+    );
+}
+
+#[test]
+fn context_flag_widens_diagnostics() {
+    let source = "1;\n2;\n3;\n4 + nil;\n5;\n6;\n7;";
+
+    let narrow = lox_file("context-narrow", source, &["--color=never"]);
+    let narrow = String::from_utf8_lossy(&narrow.stderr);
+    assert!(narrow.contains("   3 | 3;"));
+    assert!(!narrow.contains("   2 | 2;"));
+
+    let wide = lox_file("context-wide", source, &["--color=never", "--context", "3"]);
+    let wide = String::from_utf8_lossy(&wide.stderr);
+    assert!(wide.contains("   1 | 1;"));
+    assert!(wide.contains("   7 | 7;"));
+}
+
+#[test]
+fn for_loop_prints_each_iteration() {
+    let output = lox_file("for", "for (var i = 0; i < 3; i = i + 1) print i;", &[]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0\n1\n2\n");
+}
+
+#[test]
+fn summary_after_errors() {
+    let output = lox_file("summary", "print 1 + nil;", &["--color=never"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with("error: aborting due to 1 error\n"));
+
+    let clean = lox_file("summary-clean", "print 1;", &[]);
+    assert!(clean.stderr.is_empty());
+}
+
+#[test]
+fn every_broken_statement_is_reported() {
+    let output = lox_file(
+        "broken-statements",
+        "var = 1;\nprint 2;\nprint (3;\n5 6\n",
+        &["--color=never"],
+    );
+
+    assert!(!output.status.success());
+    assert!(stdout(&output).is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Error at").count(), 3);
+    assert!(stderr.ends_with("error: aborting due to 3 errors\n"));
+}
+
+#[test]
+fn parse_errors_in_source_order() {
+    let output = lox_file(
+        "source-order",
+        "print ;\nprint 1;\nvar @ = 2;",
+        &["--color=never"],
+    );
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let headers: Vec<_> = stderr
+        .lines()
+        .filter(|line| line.starts_with("Error at"))
+        .map(|line| line.split(": ").next().unwrap_or(line))
+        .collect();
+    assert_eq!(headers.len(), 3);
+    assert!(headers[0].ends_with("source-order.lox:1:7"));
+    assert!(headers[1].ends_with("source-order.lox:3:5"));
+    assert!(headers[2].ends_with("source-order.lox:3:7"));
+}
+
+#[test]
+fn recovered_operand_errors_are_rendered_once() {
+    let output = lox_file("bad-operand", "print 1 + );\nprint 2;", &["--color=never"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Error at").count(), 1);
+    assert!(stderr.contains("Parser error: expected"));
+    assert!(stderr.ends_with("error: aborting due to 1 error\n"));
+}
+
+#[test]
+fn functions_return_values() {
+    let output = lox_file(
+        "functions",
+        "fun add(a, b) { return a + b; }\nprint add(1, 2);\nprint add;",
+        &[],
+    );
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n<fn add>\n");
+}
+
+#[test]
+fn chained_comparison_warns() {
+    let output = lox_file(
+        "chained-comparison",
+        "1 < 2 < 3;",
+        &["--color=never", "--ast"],
+    );
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "(< (< 1 2) 3)\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning at"));
+    assert!(stderr.contains("comparisons cannot be chained; did you mean"));
+    assert!(stderr.ends_with("warning: 1 warning emitted\n"));
+}
+
+#[test]
+fn unclosed_paren_points_at_the_opening_one() {
+    let output = lox_file("unclosed-paren", "(1 + (2 * 3;", &["--color=never"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(":1:12: Parser error: expected ')' to close '(', found ';'"));
+    assert!(stderr.contains(concat!(
+        "   1 | (1 + (2 * 3;\n",
+        "                   ^\n",
+        "             - opened here\n",
+    )));
+}
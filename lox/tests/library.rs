@@ -0,0 +1,22 @@
+use lox::{ast, interpreter::Interpreter, parser::Parser, scanner::Scanner};
+
+#[test]
+fn parse_and_run_through_the_library() {
+    let source = "var a = 1;\nwhile (a < 3) a = a + 1;";
+    let tokens: Vec<_> = Scanner::new(source)
+        .significant()
+        .map(|t| t.expect("The source is valid"))
+        .collect();
+
+    let program = Parser::new(&tokens, source)
+        .parse_program()
+        .expect("The source parses");
+    assert_eq!(
+        ast::print_program(&program),
+        "(var a 1)\n(while (< a 3) (= a (+ a 1)))\n"
+    );
+
+    Interpreter::default()
+        .interpret(&program)
+        .expect("The program runs");
+}